@@ -55,8 +55,19 @@
 
           nativeBuildInputs = with pkgs; [
             pkg-config
+            gettext
           ];
 
+          LOCALEDIR = "${placeholder "out"}/share/locale";
+
+          postInstall = ''
+            for po in po/*.po; do
+              lang=$(basename $po .po)
+              mkdir -p $out/share/locale/$lang/LC_MESSAGES
+              msgfmt -o $out/share/locale/$lang/LC_MESSAGES/egui-greeter.mo $po
+            done
+          '';
+
           fixupPhase = ''
             patchelf --set-rpath ${builtins.toString (pkgs.lib.makeLibraryPath buildInputs)} $out/bin/egui-greeter
           '';
@@ -116,6 +127,9 @@
 
               services.seatd.enable = true;
 
+              # the greeter remembers the last user and their session here
+              systemd.tmpfiles.rules = [ "d /var/cache/egui-greeter 0755 greeter greeter -" ];
+
               services.greetd = {
                 enable = true;
                 settings = {
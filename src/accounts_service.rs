@@ -0,0 +1,51 @@
+use std::{fs, path::PathBuf};
+
+const USERS_DIR: &str = "/var/lib/AccountsService/users";
+
+/// per user preferences other display managers store through AccountsService
+#[derive(Default, Debug, Clone)]
+pub struct AccountsServiceUser {
+    /// desktop file name of the session without the .desktop extension
+    pub session: Option<String>,
+    pub icon: Option<PathBuf>,
+}
+
+pub fn read_user(name: &str) -> AccountsServiceUser {
+    let Ok(keyfile) = fs::read_to_string(PathBuf::from(USERS_DIR).join(name)) else {
+        return AccountsServiceUser::default();
+    };
+
+    let mut user = AccountsServiceUser::default();
+    let mut in_user_section = false;
+
+    for line in keyfile.lines().map(str::trim) {
+        if line.starts_with('[') {
+            in_user_section = line == "[User]";
+            continue;
+        }
+
+        if !in_user_section {
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            continue;
+        };
+
+        let value = value.trim();
+
+        if value.is_empty() {
+            continue;
+        }
+
+        match key.trim() {
+            "Session" => user.session = Some(value.to_string()),
+            // only used when there is no Session key
+            "XSession" if user.session.is_none() => user.session = Some(value.to_string()),
+            "Icon" => user.icon = Some(PathBuf::from(value)).filter(|icon| icon.is_file()),
+            _ => {}
+        }
+    }
+
+    user
+}
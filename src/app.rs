@@ -1,38 +1,145 @@
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 
+use anyhow::Result;
 use eframe::CreationContext;
 use egui::{
-    Align, Align2, Color32, Direction, Frame, Stroke, ViewportCommand,
-    epaint::text::{FontInsert, InsertFontFamily},
+    Align, Align2, Color32, Direction, Frame, Rect, RichText, Stroke, Vec2, ViewportCommand,
+    WidgetText,
 };
 use egui_toast::{Toast, ToastKind, ToastOptions, Toasts};
 
 use crate::{
-    auth_thread::{self, Handle, InputType},
-    inputs::basic_center_input,
+    accounts_service::{self, AccountsServiceUser},
+    auth_thread::{self, AuthError, AuthState, Handle, InputType, MessageKind},
+    avatar::{Avatars, avatar_area},
+    background::Background,
+    fonts::FontSizes,
+    inputs::{
+        basic_center_input, failure_message, session_input, unavailable_message, user_input,
+        user_list,
+    },
+    keyboard::Keyboard,
+    layout::{self, Component, LayoutEntry, is_placed, mirror_layout},
+    outputs::{OutputConfig, output_rects, primary_index},
     power_area::power_area,
+    scale::ScaleConfig,
     search_selector::{SelectorCache, SingleCache},
     sessions::get_sessions,
-    settings::Settings,
-    time_area::time_area,
+    settings::{Settings, UserEntry},
+    state_cache::StateCache,
+    theme::Palette,
+    time_area::{ClockConfig, time_area},
+    users::{User, get_users},
 };
 
+const CARD_CORNER_RADIUS: f32 = 20.0;
+/// how soon to try again to send commands that didn't fit in the channel
+const OUTBOX_RETRY: Duration = Duration::from_millis(50);
+
 pub struct DisplayState {
     pub session_input: String,
     pub search_cache: SelectorCache<(String, String)>,
+    pub user_input: String,
+    pub user_cache: SelectorCache<Option<String>>,
     pub input: String,
-    pub input_type: Option<InputType>,
     pub session: (String, String),
+    pub user: Option<String>,
+    pub account: AccountsServiceUser,
+    pub avatars: Avatars,
+    pub cache: StateCache,
+    pub keyboard: Keyboard,
+    /// the last state from the auth thread, the login card is drawn from it
+    pub auth: AuthState,
+    /// a command was sent and the auth thread hasn't answered yet, so there is nothing to input
+    pub waiting: bool,
+    /// the messages from pam in this attempt, the last one is for the current input
+    pub conversation: Vec<(MessageKind, String)>,
+    /// show the typed password, reset for every new prompt
+    pub reveal_password: bool,
 }
 
 impl DisplayState {
-    pub fn new(default: String, default_command: String) -> Self {
-        Self {
+    pub fn new(
+        user: Option<String>,
+        cache: StateCache,
+        keyboard: Keyboard,
+        sessions: &[(Option<PathBuf>, (String, String))],
+    ) -> Self {
+        // get_sessions always returns at least one session
+        let (_, session) = sessions[0].clone();
+
+        let mut state = Self {
             input: String::new(),
-            input_type: None,
-            session: (default.clone(), default_command),
-            session_input: default,
+            session_input: session.0.clone(),
+            session,
             search_cache: SingleCache::default(),
+            user_input: user.clone().unwrap_or_default(),
+            user_cache: SingleCache::default(),
+            user,
+            account: AccountsServiceUser::default(),
+            avatars: Avatars::default(),
+            cache,
+            keyboard,
+            auth: AuthState::Idle,
+            waiting: false,
+            conversation: Vec::new(),
+            reveal_password: false,
+        };
+
+        state.load_user_preferences(sessions);
+
+        state
+    }
+
+    /// load the preferences of the current user, and switch to the session they last logged in
+    /// with here, or otherwise the one AccountsService has for them
+    pub fn load_user_preferences(&mut self, sessions: &[(Option<PathBuf>, (String, String))]) {
+        let Some(user) = self.user.as_deref() else {
+            return;
+        };
+
+        self.account = accounts_service::read_user(user);
+
+        let cached = self
+            .cache
+            .session_for(user)
+            .and_then(|name| sessions.iter().find(|(_, (n, _))| n == name));
+
+        let from_account = self.account.session.as_deref().and_then(|desktop_name| {
+            sessions.iter().find(|(path, _)| {
+                path.as_ref()
+                    .and_then(|path| path.file_stem())
+                    .is_some_and(|stem| stem == desktop_name)
+            })
+        });
+
+        if let Some((_, session)) = cached.or(from_account) {
+            self.session = session.clone();
+            self.session_input = session.0.clone();
+        }
+
+        if let Some(layout) = self.cache.keyboard_layout_for(user) {
+            self.keyboard.select(layout);
+        }
+    }
+
+    /// forget what was entered in the current attempt, when the login starts over
+    pub fn reset_attempt(&mut self) {
+        self.input.clear();
+        self.conversation.clear();
+        self.reveal_password = false;
+    }
+
+    fn record_login(&mut self) {
+        let Some(user) = &self.user else {
+            return;
+        };
+
+        self.cache
+            .record_login(user, &self.session.0, self.keyboard.active());
+
+        if let Err(err) = self.cache.save() {
+            log::warn!("{err:#}");
         }
     }
 }
@@ -40,36 +147,50 @@ impl DisplayState {
 pub struct DisplayManager<'a> {
     pub handle: &'a mut Handle,
     pub sessions: Vec<(Option<PathBuf>, (String, String))>,
+    pub users: Vec<User>,
+    pub user_entry: UserEntry,
+    pub password_reveal: bool,
+    pub auto_acknowledge_info: bool,
+    pub palette: Palette,
+    pub font_sizes: FontSizes,
+    /// mirror the layout for right to left languages
+    pub rtl: bool,
+    pub clock: ClockConfig,
+    pub background: Background,
+    pub layout: Vec<LayoutEntry>,
+    pub hostname: String,
+    pub outputs: Vec<OutputConfig>,
+    /// index of the output the login card is on
+    pub primary: usize,
+    /// move the login card to whichever output the mouse is on
+    pub follow_mouse: bool,
+    pub scale: ScaleConfig,
     pub state: DisplayState,
 }
 
 impl<'a> DisplayManager<'a> {
-    pub fn new(settings: Settings, handle: &'a mut Handle, cc: &CreationContext) -> Self {
-        let sessions = get_sessions(
-            settings.default_session_name.clone(),
-            settings.default_session_command.clone(),
-        );
+    pub fn new(
+        settings: Settings,
+        cache: StateCache,
+        handle: &'a mut Handle,
+        rtl: bool,
+        cc: &CreationContext,
+    ) -> Self {
+        let sessions = get_sessions(settings.default_session());
+
+        let users = get_users(settings.min_uid, settings.max_uid);
 
         let ctx = &cc.egui_ctx;
 
-        catppuccin_egui::set_theme(ctx, catppuccin_egui::MACCHIATO);
-
-        ctx.add_font(FontInsert::new(
-            "FiraCode",
-            egui::FontData::from_static(include_bytes!(
-                "../fonts/FiraCodeNerdFontMono-Regular.ttf"
-            )),
-            vec![
-                InsertFontFamily {
-                    family: egui::FontFamily::Monospace,
-                    priority: egui::epaint::text::FontPriority::Highest,
-                },
-                InsertFontFamily {
-                    family: egui::FontFamily::Proportional,
-                    priority: egui::epaint::text::FontPriority::Lowest,
-                },
-            ],
-        ));
+        handle.set_context(ctx);
+
+        let palette = settings.theme.palette();
+
+        settings.theme.apply(ctx, &palette);
+
+        let background = Background::load(&settings.background, ctx);
+
+        settings.fonts.apply(ctx);
 
         ctx.style_mut(|style| {
             style.visuals.widgets.hovered.bg_stroke = Stroke::new(0.0, Color32::from_rgb(0, 0, 0));
@@ -79,123 +200,324 @@ impl<'a> DisplayManager<'a> {
             style.visuals.widgets.noninteractive.bg_stroke =
                 Stroke::new(0.0, Color32::from_rgb(0, 0, 0));
             style.visuals.window_stroke = Stroke::new(0.0, Color32::from_rgb(0, 0, 0));
-
-            style.override_font_id = Some(egui::FontId {
-                size: 18.0,
-                family: egui::FontFamily::Monospace,
-            });
         });
 
+        let state = DisplayState::new(
+            settings.user,
+            cache,
+            Keyboard::new(&settings.keyboard),
+            &sessions,
+        );
+
+        let mut layout = settings.layout;
+
+        if rtl {
+            mirror_layout(&mut layout);
+        }
+
         Self {
             handle,
             sessions,
-            state: DisplayState::new(
-                settings.default_session_name,
-                settings.default_session_command,
-            ),
+            users,
+            user_entry: settings.user_entry,
+            password_reveal: settings.password_reveal,
+            auto_acknowledge_info: settings.auto_acknowledge_info,
+            palette,
+            font_sizes: settings.fonts.sizes,
+            rtl,
+            clock: settings.clock,
+            background,
+            layout,
+            hostname: layout::hostname(),
+            primary: primary_index(&settings.outputs, settings.primary_output.as_deref()),
+            follow_mouse: settings.primary_output.is_none(),
+            outputs: settings.outputs,
+            scale: settings.scale,
+            state,
+        }
+    }
+
+    /// show the error screen, or just a toast if the command didn't fit the state
+    fn fail(&mut self, err: anyhow::Error, toasts: &mut Toasts) {
+        if let Some(AuthError::OutOfPhase(err)) = err.downcast_ref() {
+            log::warn!("{err}");
+            error_toast(toasts, err.to_string());
+            return;
+        }
+
+        log::error!("{err:#}");
+        self.state.auth = AuthState::Failed(err.to_string());
+    }
+
+    /// start a new auth thread and go back to the first prompt
+    fn restart_login(&mut self) {
+        self.state.auth = AuthState::Idle;
+        self.state.waiting = false;
+        self.state.reset_attempt();
+
+        if let Err(err) = self.handle.restart(self.state.user.clone()) {
+            log::error!("{err}");
+            self.state.auth = AuthState::Failed(err.to_string());
+        }
+    }
+
+    fn login_card(&mut self, ui: &mut egui::Ui) -> Result<()> {
+        match &self.state.auth {
+            AuthState::Failed(err) => {
+                if failure_message(err, &self.palette, ui) {
+                    self.restart_login();
+                }
+                return Ok(());
+            }
+            AuthState::Unavailable { reason, retrying } => {
+                unavailable_message(reason, *retrying, &self.palette, ui);
+                return Ok(());
+            }
+            _ => {}
+        }
+
+        avatar_area(&mut self.state, &self.users, &self.palette, ui);
+
+        if !is_placed(&self.layout, &Component::UserList) {
+            user_input(
+                &mut self.state,
+                &self.sessions,
+                &self.users,
+                self.user_entry,
+                &self.palette,
+                self.handle,
+                ui,
+            )?;
+        }
+
+        if !is_placed(&self.layout, &Component::SessionSelector) {
+            session_input(&mut self.state, &self.sessions, &self.palette, ui);
+        }
+
+        basic_center_input(
+            &mut self.state,
+            &self.palette,
+            self.password_reveal,
+            self.handle,
+            ui,
+        )
+    }
+
+    fn label(&self, text: &str, size: Option<f32>, ui: &mut egui::Ui) {
+        ui.label(
+            RichText::new(text)
+                .color(self.palette.text)
+                .size(size.unwrap_or(self.font_sizes.inputs)),
+        );
+    }
+
+    fn component(&mut self, component: &Component, toasts: &mut Toasts, ui: &mut egui::Ui) {
+        match component {
+            Component::Clock => {
+                ui.with_layout(egui::Layout::top_down(Align::Center), |ui| {
+                    time_area(ui, &self.clock, &self.palette, &self.font_sizes)
+                });
+            }
+            Component::LoginCard => {
+                Frame::new()
+                    .inner_margin(20)
+                    .fill(self.palette.card)
+                    .corner_radius(CARD_CORNER_RADIUS)
+                    .show(ui, |ui| {
+                        ui.with_layout(egui::Layout::top_down(Align::Center), |ui| {
+                            if let Err(err) = self.login_card(ui) {
+                                self.fail(err, toasts);
+                            }
+                        });
+                    });
+            }
+            Component::PowerMenu => match power_area(ui, &self.palette, &self.font_sizes, self.rtl)
+            {
+                Ok(()) => {}
+                Err(err) => error_toast(toasts, err.to_string()),
+            },
+            Component::SessionSelector => {
+                session_input(&mut self.state, &self.sessions, &self.palette, ui);
+            }
+            Component::UserList => {
+                if let Err(err) = user_list(
+                    &mut self.state,
+                    &self.sessions,
+                    &self.users,
+                    self.user_entry,
+                    &self.palette,
+                    self.handle,
+                    ui,
+                ) {
+                    self.fail(err, toasts);
+                }
+            }
+            Component::Hostname { size } => self.label(&self.hostname, *size, ui),
+            Component::Text { text, size } => self.label(text, *size, ui),
+        }
+    }
+
+    /// react to a new state from the auth thread, answering the steps that need no input
+    fn auth_changed(&mut self, auth: &AuthState, ctx: &egui::Context, toasts: &mut Toasts) {
+        let command = match auth {
+            AuthState::Prompting {
+                kind,
+                message,
+                input,
+            } => {
+                self.state.conversation.push((*kind, message.clone()));
+                self.state.reveal_password = false;
+
+                let acknowledge = self.auto_acknowledge_info
+                    && *kind == MessageKind::Info
+                    && *input == InputType::None;
+
+                acknowledge.then_some(auth_thread::Command::Next)
+            }
+            AuthState::AwaitingSession => {
+                Some(auth_thread::Command::Session(self.state.session.1.clone()))
+            }
+            AuthState::Unavailable { .. } => {
+                // the login starts from the beginning once it is back
+                self.state.reset_attempt();
+                None
+            }
+            AuthState::Done => {
+                self.state.record_login();
+                ctx.send_viewport_cmd(ViewportCommand::Close);
+                None
+            }
+            AuthState::Idle | AuthState::Starting | AuthState::Failed(_) => None,
+        };
+
+        if let Some(command) = command {
+            match self.handle.send_command(command) {
+                Ok(()) => self.state.waiting = true,
+                Err(err) => error_toast(toasts, err.to_string()),
+            }
+        }
+    }
+
+    /// draw one output's part of the window, the secondary outputs only get the background and
+    /// clock
+    fn show_output(
+        &mut self,
+        ctx: &egui::Context,
+        index: usize,
+        screen: Rect,
+        toasts: &mut Toasts,
+    ) {
+        let primary = index == self.primary;
+
+        self.background.paint(ctx, screen);
+
+        for (entry_index, entry) in self.layout.clone().iter().enumerate() {
+            if !entry.visible || !(primary || entry.component == Component::Clock) {
+                continue;
+            }
+
+            // the primary output keeps the same ids wherever it is, so the focus follows the card
+            let id = if primary {
+                egui::Id::new(("layout", entry_index))
+            } else {
+                egui::Id::new(("layout", entry_index, index))
+            };
+
+            let align = entry.anchor.align();
+
+            let area = egui::Area::new(id)
+                .pivot(align)
+                .fixed_pos(align.pos_in_rect(&screen) + Vec2::from(entry.offset))
+                .constrain_to(screen)
+                .show(ctx, |ui| self.component(&entry.component, toasts, ui));
+
+            if entry.component == Component::LoginCard {
+                self.background
+                    .paint_behind(ctx, screen, area.response.rect, CARD_CORNER_RADIUS);
+            }
         }
     }
 }
 
+fn error_toast(toasts: &mut Toasts, text: impl Into<WidgetText>) {
+    toasts.add(Toast {
+        kind: ToastKind::Error,
+        text: text.into(),
+        options: ToastOptions::default()
+            .duration_in_seconds(5.0)
+            .show_progress(true),
+        ..Default::default()
+    });
+}
+
 impl eframe::App for DisplayManager<'_> {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let screens = output_rects(&self.outputs, ctx.screen_rect());
+
+        if self.follow_mouse
+            && let Some(pos) = ctx.input(|i| i.pointer.hover_pos())
+            && let Some(index) = screens.iter().position(|screen| screen.contains(pos))
+        {
+            self.primary = index;
+        }
+
+        let primary = screens[self.primary.min(screens.len() - 1)];
+
+        // the toasts are anchored to the top of the screen, moved to the top of the primary output
         let mut toasts = Toasts::new()
-            .anchor(Align2::CENTER_TOP, (0.0, 10.0))
+            .anchor(
+                Align2::CENTER_TOP,
+                (primary.center_top() - ctx.screen_rect().center_top()).to_pos2()
+                    + Vec2::new(0.0, 10.0),
+            )
             .direction(Direction::TopDown);
 
+        if let Err(err) = self.handle.flush() {
+            self.fail(err.into(), &mut toasts);
+        }
+
         while let Some(mes) = self.handle.get_response() {
             match mes {
-                auth_thread::Responce::Success => ctx.send_viewport_cmd(ViewportCommand::Close),
                 auth_thread::Responce::Error(err) => {
-                    toasts.add(Toast {
-                        kind: ToastKind::Error,
-                        text: err.into(),
-                        options: ToastOptions::default()
-                            .duration_in_seconds(5.0)
-                            .show_progress(true),
-                        ..Default::default()
-                    });
+                    // the attempt failed, greetd starts again from the first prompt
+                    self.state.conversation.clear();
+                    self.state.waiting = false;
+
+                    error_toast(&mut toasts, err);
                 }
-                auth_thread::Responce::Message(mes) => {
-                    toasts.add(Toast {
-                        kind: ToastKind::Info,
-                        text: mes.into(),
-                        options: ToastOptions::default()
-                            .duration_in_seconds(5.0)
-                            .show_progress(true),
-                        ..Default::default()
-                    });
+                auth_thread::Responce::Rejected(err) => {
+                    // the rejected command won't change the state, so stop waiting for it
+                    self.state.waiting = false;
+
+                    error_toast(&mut toasts, err.to_string());
                 }
-                auth_thread::Responce::GetInput(t) => self.state.input_type = Some(t),
-                auth_thread::Responce::GetSession => match self
-                    .handle
-                    .send_command(auth_thread::Command::Session(self.state.session.1.clone()))
-                {
-                    Ok(()) => {}
-                    Err(err) => {
-                        toasts.add(Toast {
-                            kind: ToastKind::Error,
-                            text: err.to_string().into(),
-                            options: ToastOptions::default()
-                                .duration_in_seconds(5.0)
-                                .show_progress(true),
-                            ..Default::default()
-                        });
-                    }
-                },
             }
         }
 
-        egui::CentralPanel::default().show(ctx, |_| {});
+        if let Some(auth) = self.handle.state_changed() {
+            self.state.waiting = false;
+            self.auth_changed(&auth, ctx, &mut toasts);
+            self.state.auth = auth;
+        }
 
-        egui::Area::new(egui::Id::new("center input"))
-            .anchor(Align2::CENTER_CENTER, [0.0, 0.0])
-            .show(ctx, |ui| {
-                Frame::new()
-                    .inner_margin(20)
-                    .fill(Color32::from_rgb(54, 58, 79))
-                    .corner_radius(20.0)
-                    .show(ui, |ui| {
-                        ui.with_layout(egui::Layout::top_down(Align::Center), |ui| {
-                            match basic_center_input(
-                                &mut self.state,
-                                &self.sessions,
-                                self.handle,
-                                ui,
-                            ) {
-                                Ok(()) => {}
-                                Err(_) => {
-                                    panic!("invalid state occurred")
-                                }
-                            }
-                        });
-                    });
-            });
+        self.state.keyboard.process_input(ctx);
 
-        egui::Area::new(egui::Id::new("power"))
-            .anchor(Align2::RIGHT_BOTTOM, [-20.0, 10.0])
-            .show(ctx, |ui| match power_area(ui) {
-                Ok(()) => {}
-                Err(err) => {
-                    toasts.add(Toast {
-                        kind: ToastKind::Error,
-                        text: err.to_string().into(),
-                        options: ToastOptions::default()
-                            .duration_in_seconds(5.0)
-                            .show_progress(true),
-                        ..Default::default()
-                    });
-                }
-            });
+        // everything is sized for the output with the login card on it
+        self.scale.apply(ctx, primary);
 
-        egui::Area::new(egui::Id::new("time"))
-            .anchor(Align2::LEFT_BOTTOM, [20.0, 10.0])
-            .show(ctx, |ui| {
-                ui.with_layout(egui::Layout::top_down(Align::Center), time_area)
-            });
+        egui::CentralPanel::default().show(ctx, |_| {});
+
+        for (index, screen) in screens.into_iter().enumerate() {
+            self.show_output(ctx, index, screen, &mut toasts);
+        }
 
         toasts.show(ctx);
 
-        ctx.request_repaint_after_secs(1.0);
+        // the auth thread wakes up the ui itself, so only the clock and the outbox need a timer
+        ctx.request_repaint_after(self.clock.until_next_tick());
+
+        if self.handle.pending() {
+            ctx.request_repaint_after(OUTBOX_RETRY);
+        }
     }
 }
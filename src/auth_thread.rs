@@ -1,123 +1,469 @@
 use std::{
-    env,
+    collections::VecDeque,
+    env, fmt,
+    pin::pin,
+    sync::{Arc, OnceLock},
     thread::{self, JoinHandle},
+    time::Duration,
 };
 
-use anyhow::{Context, Result, anyhow};
+use gettextrs::gettext;
 use greetd_ipc::codec::TokioCodec;
+use thiserror::Error;
 use tokio::{
     net::UnixStream,
     runtime::{self},
-    sync::mpsc::{self, Receiver, Sender},
+    sync::{
+        mpsc::{
+            self, Receiver, Sender,
+            error::{SendError, TrySendError},
+        },
+        watch,
+    },
+    time::{self, Instant},
 };
 
+/// the first wait before trying greetd again, doubled after every failed attempt
+const RETRY_MIN: Duration = Duration::from_millis(250);
+const RETRY_MAX: Duration = Duration::from_secs(10);
+
+/// how long to wait before trying greetd again. kept over reconnects and only reset once greetd
+/// gets as far as asking something, so a greetd that keeps failing straight away isn't hammered
+struct Backoff {
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new() -> Backoff {
+        Backoff { delay: RETRY_MIN }
+    }
+
+    /// the wait for this attempt, the next one waits twice as long
+    fn next(&mut self) -> Duration {
+        let delay = self.delay;
+        self.delay = (delay * 2).min(RETRY_MAX);
+        delay
+    }
+
+    fn reset(&mut self) {
+        self.delay = RETRY_MIN;
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum AuthError {
+    Start(std::io::Error),
+    Disconnected(String),
+    Protocol(String),
+    OutOfPhase(#[from] PhaseError),
+    UiClosed,
+    Stopped,
+}
+
+// written out so the messages can be translated, they are shown in the login card
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            AuthError::Start(err) => {
+                gettext("failed to start the auth thread: {}").replace("{}", &err.to_string())
+            }
+            AuthError::Disconnected(err) => {
+                gettext("lost the connection to greetd: {}").replace("{}", err)
+            }
+            AuthError::Protocol(err) => {
+                gettext("unexpected message from greetd: {}").replace("{}", err)
+            }
+            AuthError::OutOfPhase(err) => err.to_string(),
+            AuthError::UiClosed => gettext("the login screen closed"),
+            AuthError::Stopped => gettext("the auth thread stopped"),
+        };
+
+        f.write_str(&message)
+    }
+}
+
+impl From<greetd_ipc::codec::Error> for AuthError {
+    fn from(err: greetd_ipc::codec::Error) -> Self {
+        match err {
+            greetd_ipc::codec::Error::Io(err) => AuthError::Disconnected(err),
+            greetd_ipc::codec::Error::Eof => AuthError::Disconnected("EOF".to_string()),
+            greetd_ipc::codec::Error::Serialization(err) => AuthError::Protocol(err),
+        }
+    }
+}
+
+impl<T> From<SendError<T>> for AuthError {
+    fn from(_: SendError<T>) -> Self {
+        AuthError::UiClosed
+    }
+}
+
+/// a command that the auth thread doesn't take in its current state
+#[derive(Debug, Error, Clone, Copy, PartialEq)]
+pub enum PhaseError {
+    NeedUser,
+    NeedInput,
+    NoInputNeeded,
+    NoSessionNeeded,
+    NothingToCancel,
+}
+
+// written out so the messages can be translated
+impl fmt::Display for PhaseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            PhaseError::NeedUser => gettext("need a username first"),
+            PhaseError::NeedInput => gettext("need a password"),
+            PhaseError::NoInputNeeded => gettext("no input needed now"),
+            PhaseError::NoSessionNeeded => gettext("don't need session yet"),
+            PhaseError::NothingToCancel => gettext("nothing to cancel"),
+        };
+
+        f.write_str(&message)
+    }
+}
+
+type Result<T> = std::result::Result<T, AuthError>;
+
 pub enum Command {
     Quit,
+    /// answers a prompt that takes input
     Entered(String),
+    /// acknowledges a prompt without input
     Next,
+    /// the session command to start, once authenticated
     Session(String),
+    /// log in as someone else, starting over. taken in every state
+    User(String),
+    /// give up on the current attempt and start over with the same user
+    Cancel,
 }
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub enum InputType {
     None,
     Password,
     Visible,
 }
 
+/// what a message from pam is, so they can be shown differently
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum MessageKind {
+    /// asks for input
+    Prompt,
+    Info,
+    Error,
+}
+
+/// where the login is, published by the auth thread after every step so the ui can be drawn from
+/// it. every command that isn't rejected changes it, or at least marks it as changed
+#[derive(Clone, PartialEq, Debug, Default)]
+pub enum AuthState {
+    /// waiting for greetd or for a user to be picked, nothing to answer
+    #[default]
+    Idle,
+    /// greetd can't be reached, and whether it will keep trying to reconnect
+    Unavailable { reason: String, retrying: bool },
+    /// a message from pam, answered with `Entered` if it takes input and `Next` otherwise
+    Prompting {
+        kind: MessageKind,
+        message: String,
+        input: InputType,
+    },
+    /// authenticated, answered with `Session`
+    AwaitingSession,
+    /// the session was sent to greetd
+    Starting,
+    /// the session was started, the greeter should close
+    Done,
+    /// the auth thread stopped after an error, it has to be restarted to log in
+    Failed(String),
+}
+
+impl AuthState {
+    /// whether the auth thread takes the command in this state
+    pub fn check(&self, command: &Command) -> std::result::Result<(), PhaseError> {
+        match (self, command) {
+            (_, Command::Quit | Command::User(_)) => Ok(()),
+            (
+                AuthState::Idle | AuthState::Prompting { .. } | AuthState::AwaitingSession,
+                Command::Cancel,
+            ) => Ok(()),
+            (_, Command::Cancel) => Err(PhaseError::NothingToCancel),
+            (AuthState::Prompting { input, .. }, Command::Entered(_)) => match input {
+                InputType::None => Err(PhaseError::NoInputNeeded),
+                InputType::Password | InputType::Visible => Ok(()),
+            },
+            (AuthState::Prompting { input, .. }, Command::Next) => match input {
+                InputType::None => Ok(()),
+                InputType::Password | InputType::Visible => Err(PhaseError::NeedInput),
+            },
+            (AuthState::AwaitingSession, Command::Session(_)) => Ok(()),
+            (_, Command::Session(_)) => Err(PhaseError::NoSessionNeeded),
+            (_, Command::Entered(_) | Command::Next) => Err(PhaseError::NoInputNeeded),
+        }
+    }
+}
+
+/// things that happened, unlike the state these are not lost when more happen before the ui looks
 pub enum Responce {
-    Success, // should close
+    /// the attempt failed, greetd starts over from the first prompt
     Error(String),
-    Message(String),
-    GetInput(InputType),
-    GetSession,
+    /// a command didn't fit the state, the attempt goes on as before
+    Rejected(PhaseError),
 }
 
 pub struct Handle {
     pub send: mpsc::Sender<Command>,
     pub recieve: mpsc::Receiver<Responce>,
+    pub state: watch::Receiver<AuthState>,
     pub join: JoinHandle<()>,
+    /// commands that didn't fit in the channel yet, so the ui never blocks on the auth thread
+    outbox: VecDeque<Command>,
+    /// shared with the auth thread, which wakes up the ui when it has something new
+    repaint: Arc<OnceLock<egui::Context>>,
 }
 
 impl Handle {
-    pub fn make_handle<F>(user: String, f: F) -> Result<()>
+    pub fn make_handle<F>(user: Option<String>, f: F) -> anyhow::Result<()>
     where
-        F: FnOnce(&mut Handle) -> Result<()>,
+        F: FnOnce(&mut Handle) -> anyhow::Result<()>,
     {
-        let mut handle = run(user).with_context(|| "while starting background thread")?;
+        let mut handle = run(user, Arc::default())?;
 
-        match f(&mut handle) {
-            Ok(()) => {}
-            Err(err) => {
-                handle.close()?;
-                return Err(err);
-            }
-        };
+        let result = f(&mut handle);
 
-        handle.close()?;
+        handle.close();
 
-        Ok(())
+        result
     }
 
-    fn close(mut self) -> Result<()> {
+    fn close(mut self) {
         let _ = self.send.blocking_send(Command::Quit);
 
         while self.recieve.blocking_recv().is_some() {}
 
-        self.join.join().unwrap();
+        if self.join.join().is_err() {
+            log::error!("the auth thread panicked");
+        }
+    }
+
+    /// replace the auth thread with a new one, to start over after it failed
+    pub fn restart(&mut self, user: Option<String>) -> Result<()> {
+        let old = std::mem::replace(self, run(user, self.repaint.clone())?);
+
+        old.close();
 
         Ok(())
     }
 
+    /// repaint the ui whenever the auth thread has something new
+    pub fn set_context(&self, ctx: &egui::Context) {
+        let _ = self.repaint.set(ctx.clone());
+    }
+
+    /// queue a command if the current state takes it, and send as much of the queue as fits
     pub fn send_command(&mut self, command: Command) -> Result<()> {
-        self.send.blocking_send(command)?;
+        self.state.borrow().check(&command)?;
+
+        self.outbox.push_back(command);
+
+        self.flush()
+    }
+
+    /// send the queued commands without blocking, what doesn't fit stays queued
+    pub fn flush(&mut self) -> Result<()> {
+        while let Some(command) = self.outbox.pop_front() {
+            match self.send.try_send(command) {
+                Ok(()) => {}
+                Err(TrySendError::Full(command)) => {
+                    self.outbox.push_front(command);
+                    break;
+                }
+                Err(TrySendError::Closed(_)) => return Err(AuthError::Stopped),
+            }
+        }
 
         Ok(())
     }
 
+    /// whether there are commands left to send
+    pub fn pending(&self) -> bool {
+        !self.outbox.is_empty()
+    }
+
     pub fn get_response(&mut self) -> Option<Responce> {
         self.recieve.try_recv().ok()
     }
+
+    /// the new state, if it changed since the last call
+    pub fn state_changed(&mut self) -> Option<AuthState> {
+        let state = self.state.borrow_and_update();
+
+        state.has_changed().then(|| state.clone())
+    }
 }
 
-fn run(user: String) -> Result<Handle> {
-    let (otx, mut trx) = mpsc::channel(4);
-    let (ttx, orx) = mpsc::channel(4);
+/// how the auth thread tells the ui about what happened
+struct Updates {
+    responce: Sender<Responce>,
+    states: watch::Sender<AuthState>,
+    repaint: Arc<OnceLock<egui::Context>>,
+}
 
-    let join = thread::spawn(move || {
-        let rt = match runtime::Builder::new_current_thread().enable_io().build() {
-            Ok(rt) => rt,
-            Err(err) => {
-                ttx.blocking_send(Responce::Error(format!(
-                    "failed to make tokio runtime with error: {err}"
-                )))
-                .unwrap();
-                return;
-            }
-        };
+impl Updates {
+    fn publish(&self, state: AuthState) {
+        self.states.send_replace(state);
+        self.repaint();
+    }
+
+    /// mark the state as changed without changing it, for the ui waiting on a command
+    fn touch(&self) {
+        self.states.send_modify(|_| {});
+        self.repaint();
+    }
+
+    async fn send(&self, responce: Responce) -> Result<()> {
+        self.responce.send(responce).await?;
+        self.repaint();
 
-        match rt.block_on(run_async(user, &mut trx, &ttx)) {
-            Ok(()) => {}
-            Err(err) => ttx
-                .blocking_send(Responce::Error(format!("{err}")))
-                .unwrap(),
+        Ok(())
+    }
+
+    fn check(&self, command: &Command) -> std::result::Result<(), PhaseError> {
+        self.states.borrow().check(command)
+    }
+
+    fn repaint(&self) {
+        // not set yet before the first frame, which looks at everything anyway
+        if let Some(ctx) = self.repaint.get() {
+            ctx.request_repaint();
         }
-    });
+    }
+}
+
+fn run(user: Option<String>, repaint: Arc<OnceLock<egui::Context>>) -> Result<Handle> {
+    let (otx, mut trx) = mpsc::channel(4);
+    let (ttx, orx) = mpsc::channel(4);
+    let (states, state) = watch::channel(AuthState::Idle);
+
+    let updates = Updates {
+        responce: ttx,
+        states,
+        repaint: repaint.clone(),
+    };
+
+    let join = thread::Builder::new()
+        .name("auth".to_string())
+        .spawn(move || {
+            let result = runtime::Builder::new_current_thread()
+                .enable_io()
+                .enable_time()
+                .build()
+                .map_err(AuthError::Start)
+                .and_then(|rt| rt.block_on(run_async(user, &mut trx, &updates)));
+
+            if let Err(err) = result {
+                log::error!("{err}");
+                updates.publish(AuthState::Failed(err.to_string()));
+            }
+        })
+        .map_err(AuthError::Start)?;
 
     Ok(Handle {
         send: otx,
         recieve: orx,
+        state,
         join,
+        outbox: VecDeque::new(),
+        repaint,
     })
 }
 
-async fn run_authflow(
-    user: String,
+/// how logging in on one connection to greetd ended
+enum Outcome {
+    /// authenticated in `run_authflow`, the session was started in `login`
+    Done,
+    Quit,
+    /// the attempt was given up while greetd was busy, it only reads the next request after
+    /// answering, so the connection has to be dropped to start over
+    Reconnect,
+}
+
+/// what came first while waiting for greetd
+enum Reply {
+    Greetd(greetd_ipc::Response),
+    Quit,
+    /// cancelled or the user changed
+    Abandoned,
+}
+
+/// wait for the answer from greetd, still taking commands from the ui in the meantime
+async fn reply(
+    user: &mut String,
     commands: &mut Receiver<Command>,
-    responce: &Sender<Responce>,
+    updates: &Updates,
     s: &mut UnixStream,
-) -> Result<bool> {
-    let mut success = false;
+) -> Result<Reply> {
+    let mut read = pin!(greetd_ipc::Response::read_from(s));
+
+    loop {
+        tokio::select! {
+            biased;
+            response = read.as_mut() => return Ok(Reply::Greetd(response?)),
+            command = next_command(commands, updates) => match command? {
+                Command::Quit => return Ok(Reply::Quit),
+                Command::User(name) => {
+                    *user = name;
+                    return Ok(Reply::Abandoned);
+                }
+                Command::Cancel => return Ok(Reply::Abandoned),
+                // nothing else gets through while greetd is busy
+                Command::Entered(_) | Command::Next | Command::Session(_) => {}
+            },
+        }
+    }
+}
+
+/// wait for the next command the current state takes, rejecting the others
+async fn next_command(commands: &mut Receiver<Command>, updates: &Updates) -> Result<Command> {
+    loop {
+        let command = commands.recv().await.ok_or(AuthError::UiClosed)?;
+
+        let checked = updates.check(&command);
+
+        match checked {
+            Ok(()) => return Ok(command),
+            Err(err) => reject(err, updates).await?,
+        }
+    }
+}
+
+async fn reject(err: PhaseError, updates: &Updates) -> Result<()> {
+    log::warn!("rejected a command: {err}");
+    updates.send(Responce::Rejected(err)).await?;
+
+    Ok(())
+}
+
+async fn cancel_session(s: &mut UnixStream) -> Result<()> {
+    greetd_ipc::Request::CancelSession.write_to(s).await?;
+
+    match greetd_ipc::Response::read_from(s).await? {
+        greetd_ipc::Response::Success => {}
+        greetd_ipc::Response::Error { description, .. } => {
+            log::warn!("failed to cancel session: {description}")
+        }
+        greetd_ipc::Response::AuthMessage { .. } => Err(AuthError::Protocol(gettext(
+            "auth message when cancelling the session",
+        )))?,
+    }
+
+    Ok(())
+}
+
+async fn restart_session(user: &str, s: &mut UnixStream) -> Result<()> {
+    cancel_session(s).await?;
 
     greetd_ipc::Request::CreateSession {
         username: user.to_string(),
@@ -125,197 +471,418 @@ async fn run_authflow(
     .write_to(s)
     .await?;
 
-    loop {
-        match greetd_ipc::Response::read_from(s).await? {
+    Ok(())
+}
+
+async fn run_authflow(
+    user: &mut String,
+    commands: &mut Receiver<Command>,
+    updates: &Updates,
+    backoff: &mut Backoff,
+    s: &mut UnixStream,
+) -> Result<Outcome> {
+    greetd_ipc::Request::CreateSession {
+        username: user.to_string(),
+    }
+    .write_to(s)
+    .await?;
+
+    // whether greetd asked anything since the session was created
+    let mut prompted = false;
+
+    'messages: loop {
+        let response = match reply(user, commands, updates, s).await? {
+            Reply::Greetd(response) => response,
+            Reply::Quit => return Ok(Outcome::Quit),
+            Reply::Abandoned => return Ok(Outcome::Reconnect),
+        };
+
+        match response {
             greetd_ipc::Response::Success => {
-                success = true;
-                break;
+                backoff.reset();
+                return Ok(Outcome::Done);
             }
             greetd_ipc::Response::Error {
                 error_type,
                 description,
             } => {
-                match error_type {
-                    greetd_ipc::ErrorType::Error => {
-                        responce.send(Responce::Error(description)).await?
-                    }
-                    greetd_ipc::ErrorType::AuthError => {
-                        responce.send(Responce::Error(description)).await?
-                    }
-                }
+                log::info!("login failed ({error_type:?}): {description}");
 
-                greetd_ipc::Request::CancelSession.write_to(s).await?;
+                updates.send(Responce::Error(description)).await?;
 
-                let socket = env::var("GREETD_SOCK").unwrap();
-
-                *s = UnixStream::connect(socket).await?;
-
-                greetd_ipc::Request::CreateSession {
-                    username: user.to_string(),
+                // creating the session failed, like with pam_nologin, so it will likely fail again
+                if !prompted && !wait_for_retry(backoff.next(), user, commands, updates).await? {
+                    return Ok(Outcome::Quit);
                 }
-                .write_to(s)
-                .await?;
+
+                // start the login over on the same connection
+                restart_session(user, s).await?;
+                prompted = false;
             }
             greetd_ipc::Response::AuthMessage {
                 auth_message_type,
                 auth_message,
             } => {
-                let resp = match auth_message_type {
-                    greetd_ipc::AuthMessageType::Visible => {
-                        responce.send(Responce::Message(auth_message)).await?;
-                        responce
-                            .send(Responce::GetInput(InputType::Visible))
-                            .await?;
-
-                        let command = commands
-                            .recv()
-                            .await
-                            .with_context(|| "should get a responce".to_string())?;
-
-                        match command {
-                            Command::Quit => {
-                                break;
-                            }
-                            Command::Entered(str) => Some(str),
-                            Command::Session(_) => Err(anyhow!("don't need session yet"))?,
-                            Command::Next => Err(anyhow!("need a password"))?,
-                        }
-                    }
-                    greetd_ipc::AuthMessageType::Secret => {
-                        responce.send(Responce::Message(auth_message)).await?;
-                        responce
-                            .send(Responce::GetInput(InputType::Password))
-                            .await?;
-
-                        let command = commands
-                            .recv()
-                            .await
-                            .with_context(|| "should get a responce".to_string())?;
-
-                        match command {
-                            Command::Quit => {
-                                break;
-                            }
-                            Command::Entered(str) => Some(str),
-                            Command::Session(_) => Err(anyhow!("don't need session yet"))?,
-                            Command::Next => Err(anyhow!("need a password"))?,
-                        }
+                let input = match auth_message_type {
+                    greetd_ipc::AuthMessageType::Visible => InputType::Visible,
+                    greetd_ipc::AuthMessageType::Secret => InputType::Password,
+                    greetd_ipc::AuthMessageType::Info => InputType::None,
+                    greetd_ipc::AuthMessageType::Error => InputType::None,
+                };
+
+                let kind = match auth_message_type {
+                    greetd_ipc::AuthMessageType::Visible | greetd_ipc::AuthMessageType::Secret => {
+                        MessageKind::Prompt
                     }
-                    greetd_ipc::AuthMessageType::Info => {
-                        responce.send(Responce::Message(auth_message)).await?;
-                        responce.send(Responce::GetInput(InputType::None)).await?;
-
-                        let command = commands
-                            .recv()
-                            .await
-                            .with_context(|| "should get a responce".to_string())?;
-
-                        match command {
-                            Command::Quit => {
-                                break;
-                            }
-                            Command::Entered(_) => Err(anyhow!("need a password"))?,
-                            Command::Session(_) => Err(anyhow!("don't need session yet"))?,
-                            Command::Next => None,
-                        }
+                    greetd_ipc::AuthMessageType::Info => MessageKind::Info,
+                    greetd_ipc::AuthMessageType::Error => MessageKind::Error,
+                };
+
+                prompted = true;
+                backoff.reset();
+
+                updates.publish(AuthState::Prompting {
+                    kind,
+                    message: auth_message,
+                    input,
+                });
+
+                // the state only lets through the answer that fits the prompt
+                let resp = match next_command(commands, updates).await? {
+                    Command::Quit => return Ok(Outcome::Quit),
+                    Command::User(name) => {
+                        *user = name;
+                        updates.publish(AuthState::Idle);
+                        restart_session(user, s).await?;
+                        prompted = false;
+                        continue 'messages;
                     }
-                    greetd_ipc::AuthMessageType::Error => {
-                        responce.send(Responce::Message(auth_message)).await?;
-                        responce.send(Responce::GetInput(InputType::None)).await?;
-
-                        let command = commands
-                            .recv()
-                            .await
-                            .with_context(|| "should get a responce".to_string())?;
-
-                        match command {
-                            Command::Quit => {
-                                break;
-                            }
-                            Command::Entered(_) => Err(anyhow!("need a password"))?,
-                            Command::Session(_) => Err(anyhow!("don't need session yet"))?,
-                            Command::Next => None,
-                        }
+                    Command::Cancel => {
+                        updates.publish(AuthState::Idle);
+                        restart_session(user, s).await?;
+                        prompted = false;
+                        continue 'messages;
                     }
+                    Command::Entered(answer) => Some(answer),
+                    Command::Next | Command::Session(_) => None,
                 };
 
+                updates.publish(AuthState::Idle);
+
                 greetd_ipc::Request::PostAuthMessageResponse { response: resp }
                     .write_to(s)
                     .await?;
             }
         }
     }
+}
 
-    Ok(success)
+async fn wait_for_user(
+    commands: &mut Receiver<Command>,
+    updates: &Updates,
+) -> Result<Option<String>> {
+    loop {
+        let command = commands.recv().await.ok_or(AuthError::UiClosed)?;
+
+        match command {
+            Command::Quit => return Ok(None),
+            Command::User(name) => return Ok(Some(name)),
+            Command::Entered(_) | Command::Next | Command::Session(_) | Command::Cancel => {
+                reject(PhaseError::NeedUser, updates).await?
+            }
+        }
+    }
 }
 
-async fn run_async(
-    user: String,
+/// wait before trying greetd again, still taking commands from the ui. returns false if it should
+/// quit instead
+async fn wait_for_retry(
+    delay: Duration,
+    user: &mut String,
     commands: &mut Receiver<Command>,
-    responce: &Sender<Responce>,
-) -> Result<()> {
-    let socket = env::var("GREETD_SOCK").unwrap();
+    updates: &Updates,
+) -> Result<bool> {
+    let deadline = Instant::now() + delay;
+
+    loop {
+        match time::timeout_at(deadline, next_command(commands, updates)).await {
+            Err(_) => return Ok(true),
+            Ok(command) => match command? {
+                Command::Quit => return Ok(false),
+                Command::User(name) => {
+                    *user = name;
+                    updates.touch();
+                }
+                // it starts over after the wait anyway
+                Command::Cancel => updates.touch(),
+                // nothing else gets through while waiting
+                Command::Entered(_) | Command::Next | Command::Session(_) => {}
+            },
+        }
+    }
+}
+
+/// connect to greetd, retrying with backoff until it works. none if the ui quit while waiting
+async fn connect(
+    socket: &str,
+    user: &mut String,
+    commands: &mut Receiver<Command>,
+    updates: &Updates,
+    backoff: &mut Backoff,
+) -> Result<Option<UnixStream>> {
+    loop {
+        match UnixStream::connect(socket).await {
+            Ok(stream) => return Ok(Some(stream)),
+            Err(err) => {
+                log::warn!("failed to connect to greetd at {socket}: {err}");
+                updates.publish(AuthState::Unavailable {
+                    reason: err.to_string(),
+                    retrying: true,
+                });
+            }
+        }
 
-    let mut s = UnixStream::connect(socket).await?;
+        if !wait_for_retry(backoff.next(), user, commands, updates).await? {
+            return Ok(None);
+        }
+    }
+}
 
+/// log in on one connection to greetd, until a session is started or the ui quits
+async fn login(
+    user: &mut String,
+    commands: &mut Receiver<Command>,
+    updates: &Updates,
+    backoff: &mut Backoff,
+    s: &mut UnixStream,
+) -> Result<Outcome> {
     loop {
-        if run_authflow(user.clone(), commands, responce, &mut s).await? {
-            responce.send(Responce::GetSession).await?;
-
-            let command = commands
-                .recv()
-                .await
-                .with_context(|| "failed to get back session responce")?;
-
-            let session = match command {
-                Command::Quit => break,
-                Command::Entered(_) => panic!("invalid state"),
-                Command::Next => panic!("invalid state"),
-                Command::Session(session) => session,
-            };
-
-            greetd_ipc::Request::StartSession {
-                cmd: vec!["sh".to_string(), "-c".to_string(), session],
-                env: vec![],
+        match run_authflow(user, commands, updates, backoff, s).await? {
+            Outcome::Done => {}
+            outcome => return Ok(outcome),
+        }
+
+        updates.publish(AuthState::AwaitingSession);
+
+        let session = match next_command(commands, updates).await? {
+            Command::Quit => return Ok(Outcome::Quit),
+            Command::User(name) => {
+                // start over with them
+                *user = name;
+                updates.publish(AuthState::Idle);
+                cancel_session(s).await?;
+                continue;
             }
-            .write_to(&mut s)
-            .await?;
+            Command::Cancel => {
+                updates.publish(AuthState::Idle);
+                cancel_session(s).await?;
+                continue;
+            }
+            Command::Session(session) => session,
+            // only a session gets through while waiting for one
+            Command::Entered(_) | Command::Next => continue,
+        };
 
-            match greetd_ipc::Response::read_from(&mut s).await? {
-                greetd_ipc::Response::Success => {
-                    responce.send(Responce::Success).await?;
-                    break;
-                }
+        updates.publish(AuthState::Starting);
 
-                greetd_ipc::Response::Error {
-                    error_type,
-                    description,
-                } => {
-                    match error_type {
-                        greetd_ipc::ErrorType::Error => {
-                            responce.send(Responce::Error(description)).await?
-                        }
-                        greetd_ipc::ErrorType::AuthError => {
-                            responce
-                                .send(Responce::Error("description".to_string()))
-                                .await?
-                        }
-                    }
-                    greetd_ipc::Request::CreateSession {
-                        username: user.clone(),
-                    }
-                    .write_to(&mut s)
-                    .await?
-                }
+        greetd_ipc::Request::StartSession {
+            cmd: vec!["sh".to_string(), "-c".to_string(), session],
+            env: vec![],
+        }
+        .write_to(s)
+        .await?;
+
+        match greetd_ipc::Response::read_from(s).await? {
+            greetd_ipc::Response::Success => {
+                updates.publish(AuthState::Done);
+                return Ok(Outcome::Done);
+            }
+
+            greetd_ipc::Response::Error { description, .. } => {
+                updates.send(Responce::Error(description)).await?;
+                updates.publish(AuthState::Idle);
+
+                // run_authflow creates a new session for the next attempt
+                cancel_session(s).await?;
+            }
+
+            greetd_ipc::Response::AuthMessage { .. } => Err(AuthError::Protocol(gettext(
+                "auth message when starting the session",
+            )))?,
+        }
+    }
+}
+
+async fn run_async(
+    user: Option<String>,
+    commands: &mut Receiver<Command>,
+    updates: &Updates,
+) -> Result<()> {
+    // without a configured user nothing is sent to greetd until one is entered
+    let mut user = match user {
+        Some(user) => user,
+        None => match wait_for_user(commands, updates).await? {
+            Some(user) => user,
+            None => return Ok(()),
+        },
+    };
+
+    let Ok(socket) = env::var("GREETD_SOCK") else {
+        updates.publish(AuthState::Unavailable {
+            reason: gettext("GREETD_SOCK is not set, the greeter has to be started by greetd"),
+            retrying: false,
+        });
+
+        // nothing will change that, so just wait for the ui to quit
+        loop {
+            match next_command(commands, updates).await? {
+                Command::Quit => return Ok(()),
+                Command::User(_) => updates.touch(),
+                // nothing else gets through while disconnected
+                Command::Entered(_) | Command::Next | Command::Session(_) | Command::Cancel => {}
+            }
+        }
+    };
 
-                greetd_ipc::Response::AuthMessage { .. } => {
-                    Err(anyhow!("got auth message when waiting for success/failure"))?
+    let mut backoff = Backoff::new();
+
+    loop {
+        let Some(mut s) = connect(&socket, &mut user, commands, updates, &mut backoff).await?
+        else {
+            return Ok(());
+        };
+
+        updates.publish(AuthState::Idle);
+
+        match login(&mut user, commands, updates, &mut backoff, &mut s).await {
+            // dropping the connection makes greetd cancel the session
+            Ok(Outcome::Reconnect) => log::info!("gave up on the login while greetd was busy"),
+            Ok(Outcome::Done | Outcome::Quit) => return Ok(()),
+            Err(err @ AuthError::Disconnected(_)) => {
+                log::warn!("{err}");
+                updates.publish(AuthState::Unavailable {
+                    reason: err.to_string(),
+                    retrying: true,
+                });
+
+                if !wait_for_retry(backoff.next(), &mut user, commands, updates).await? {
+                    return Ok(());
                 }
             }
-        } else {
-            break;
+            Err(err) => return Err(err),
         }
     }
+}
 
-    Ok(())
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompting(input: InputType) -> AuthState {
+        AuthState::Prompting {
+            kind: MessageKind::Prompt,
+            message: "Password:".to_string(),
+            input,
+        }
+    }
+
+    #[test]
+    fn check_takes_only_the_commands_for_the_state() {
+        use PhaseError::*;
+
+        let ok = Ok(());
+
+        // quit, user, cancel, entered, next, session
+        let table = [
+            (
+                AuthState::Idle,
+                [
+                    ok,
+                    ok,
+                    ok,
+                    Err(NoInputNeeded),
+                    Err(NoInputNeeded),
+                    Err(NoSessionNeeded),
+                ],
+            ),
+            (
+                AuthState::Unavailable {
+                    reason: "gone".to_string(),
+                    retrying: true,
+                },
+                [
+                    ok,
+                    ok,
+                    Err(NothingToCancel),
+                    Err(NoInputNeeded),
+                    Err(NoInputNeeded),
+                    Err(NoSessionNeeded),
+                ],
+            ),
+            (
+                prompting(InputType::Password),
+                [ok, ok, ok, ok, Err(NeedInput), Err(NoSessionNeeded)],
+            ),
+            (
+                prompting(InputType::Visible),
+                [ok, ok, ok, ok, Err(NeedInput), Err(NoSessionNeeded)],
+            ),
+            (
+                prompting(InputType::None),
+                [ok, ok, ok, Err(NoInputNeeded), ok, Err(NoSessionNeeded)],
+            ),
+            (
+                AuthState::AwaitingSession,
+                [ok, ok, ok, Err(NoInputNeeded), Err(NoInputNeeded), ok],
+            ),
+            (
+                AuthState::Starting,
+                [
+                    ok,
+                    ok,
+                    Err(NothingToCancel),
+                    Err(NoInputNeeded),
+                    Err(NoInputNeeded),
+                    Err(NoSessionNeeded),
+                ],
+            ),
+            (
+                AuthState::Done,
+                [
+                    ok,
+                    ok,
+                    Err(NothingToCancel),
+                    Err(NoInputNeeded),
+                    Err(NoInputNeeded),
+                    Err(NoSessionNeeded),
+                ],
+            ),
+            (
+                AuthState::Failed("broken".to_string()),
+                [
+                    ok,
+                    ok,
+                    Err(NothingToCancel),
+                    Err(NoInputNeeded),
+                    Err(NoInputNeeded),
+                    Err(NoSessionNeeded),
+                ],
+            ),
+        ];
+
+        for (state, expected) in table {
+            let commands = [
+                Command::Quit,
+                Command::User("alice".to_string()),
+                Command::Cancel,
+                Command::Entered("secret".to_string()),
+                Command::Next,
+                Command::Session("sway".to_string()),
+            ];
+
+            for (command, expected) in commands.iter().zip(expected) {
+                assert_eq!(state.check(command), expected, "{state:?}");
+            }
+        }
+    }
 }
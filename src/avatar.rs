@@ -0,0 +1,155 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Result, anyhow};
+use egui::{Align2, ColorImage, FontId, Sense, SizeHint, TextureHandle, Ui, Vec2};
+use image::imageops::FilterType;
+
+use crate::{app::DisplayState, theme::Palette, users::User};
+
+const AVATAR_SIZE: u32 = 256;
+const DISPLAY_SIZE: f32 = 96.0;
+
+/// decoded avatars, loaded once per user the first time they are selected
+#[derive(Default)]
+pub struct Avatars {
+    textures: HashMap<String, Option<TextureHandle>>,
+}
+
+impl Avatars {
+    fn get<F>(&mut self, ui: &Ui, user: &str, path: F) -> Option<&TextureHandle>
+    where
+        F: FnOnce() -> Option<PathBuf>,
+    {
+        self.textures
+            .entry(user.to_string())
+            .or_insert_with(|| {
+                let path = path()?;
+
+                match load_avatar(&path) {
+                    Ok(image) => Some(ui.ctx().load_texture(
+                        format!("avatar {user}"),
+                        image,
+                        Default::default(),
+                    )),
+                    Err(err) => {
+                        log::warn!("failed to load avatar {}: {err:#}", path.display());
+                        None
+                    }
+                }
+            })
+            .as_ref()
+    }
+}
+
+fn avatar_path(home: Option<&Path>, icon: Option<&Path>) -> Option<PathBuf> {
+    home.into_iter()
+        .flat_map(|home| [home.join(".face"), home.join(".face.icon")])
+        .chain(icon.map(Path::to_path_buf))
+        .find(|path| path.is_file())
+}
+
+fn is_svg(bytes: &[u8]) -> bool {
+    let start = String::from_utf8_lossy(&bytes[..bytes.len().min(256)]);
+    let start = start.trim_start();
+
+    start.starts_with("<svg") || start.starts_with("<?xml")
+}
+
+fn load_avatar(path: &Path) -> Result<ColorImage> {
+    let bytes = fs::read(path)?;
+
+    let image = if is_svg(&bytes) {
+        egui_extras::image::load_svg_bytes_with_size(
+            &bytes,
+            Some(SizeHint::Size(AVATAR_SIZE, AVATAR_SIZE)),
+        )
+        .map_err(|err| anyhow!(err))?
+    } else {
+        let image = image::load_from_memory(&bytes)?
+            .resize_to_fill(AVATAR_SIZE, AVATAR_SIZE, FilterType::Triangle)
+            .to_rgba8();
+
+        ColorImage::from_rgba_unmultiplied(
+            [image.width() as usize, image.height() as usize],
+            image.as_raw(),
+        )
+    };
+
+    Ok(crop_circle(&image))
+}
+
+/// crop the center square of the image, and make everything outside of the circle inside it
+/// transparent
+fn crop_circle(image: &ColorImage) -> ColorImage {
+    let [width, height] = image.size;
+    let side = width.min(height);
+    let (x0, y0) = ((width - side) / 2, (height - side) / 2);
+    let radius = side as f32 / 2.0;
+
+    let pixels = (0..side * side)
+        .map(|i| {
+            let (x, y) = (i % side, i / side);
+            let dx = x as f32 + 0.5 - radius;
+            let dy = y as f32 + 0.5 - radius;
+
+            // one pixel of anti aliasing around the edge
+            let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+
+            image.pixels[(y0 + y) * width + x0 + x].gamma_multiply(coverage)
+        })
+        .collect();
+
+    ColorImage {
+        size: [side, side],
+        pixels,
+    }
+}
+
+fn initials(name: &str) -> String {
+    name.split_whitespace()
+        .filter_map(|word| word.chars().next())
+        .take(2)
+        .flat_map(char::to_uppercase)
+        .collect()
+}
+
+fn initials_avatar(ui: &mut Ui, palette: &Palette, name: &str) {
+    let (rect, _) = ui.allocate_exact_size(Vec2::splat(DISPLAY_SIZE), Sense::hover());
+
+    ui.painter()
+        .circle_filled(rect.center(), DISPLAY_SIZE / 2.0, palette.accent);
+
+    ui.painter().text(
+        rect.center(),
+        Align2::CENTER_CENTER,
+        initials(name),
+        FontId::monospace(DISPLAY_SIZE / 2.5),
+        palette.on_accent,
+    );
+}
+
+/// the avatar of the selected user, or their initials if they don't have one
+pub fn avatar_area(state: &mut DisplayState, users: &[User], palette: &Palette, ui: &mut Ui) {
+    let Some(name) = state.user.as_deref() else {
+        return;
+    };
+
+    let user = users.iter().find(|user| user.name == name);
+
+    let icon = state.account.icon.as_deref();
+
+    match state.avatars.get(ui, name, || {
+        avatar_path(user.map(|user| user.home.as_path()), icon)
+    }) {
+        Some(texture) => {
+            ui.add(egui::Image::new(texture).fit_to_exact_size(Vec2::splat(DISPLAY_SIZE)));
+        }
+        None => initials_avatar(ui, palette, user.map_or(name, User::display_name)),
+    }
+
+    ui.add_space(10.0);
+}
@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use egui::{
+    Color32, ColorImage, LayerId, Rect, TextureHandle, TextureOptions, TextureWrapMode, epaint,
+    pos2,
+};
+use image::{DynamicImage, ImageReader, RgbaImage, imageops::FilterType};
+use serde::{Deserialize, Serialize};
+
+/// the blurred copy of the background is made from a version scaled down to fit in this, blurring
+/// the full size image is slow and the detail is lost anyway
+const BLUR_SIZE: u32 = 960;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum ScaleMode {
+    /// cover the whole screen, cropping the image
+    #[default]
+    Fill,
+    /// show the whole image, leaving bars of the background colour
+    Fit,
+    /// cover the whole screen, ignoring the aspect ratio of the image
+    Stretch,
+    /// show the image at its size in the center of the screen
+    Center,
+    /// repeat the image at its size from the top left corner
+    Tile,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct BackgroundConfig {
+    /// png or jpeg, the theme background colour is used if not set or it can't be loaded
+    pub image: Option<PathBuf>,
+    pub mode: ScaleMode,
+    /// blur radius (in pixels of the image) of the background showing through the login card
+    pub blur: f32,
+    /// how much to darken the background, from 0 to 1
+    pub dim: f32,
+}
+
+struct BackgroundImage {
+    texture: TextureHandle,
+    blurred: Option<TextureHandle>,
+    mode: ScaleMode,
+}
+
+/// the background image, decoded once at startup
+pub struct Background {
+    image: Option<BackgroundImage>,
+    dim: f32,
+}
+
+fn color_image(image: &RgbaImage) -> ColorImage {
+    ColorImage::from_rgba_unmultiplied(
+        [image.width() as usize, image.height() as usize],
+        image.as_raw(),
+    )
+}
+
+fn load_image(
+    path: &Path,
+    config: &BackgroundConfig,
+    ctx: &egui::Context,
+) -> Result<BackgroundImage> {
+    let mut image: DynamicImage = ImageReader::open(path)?.with_guessed_format()?.decode()?;
+
+    let max_size = ctx.input(|i| i.max_texture_side) as u32;
+
+    if image.width() > max_size || image.height() > max_size {
+        image = image.resize(max_size, max_size, FilterType::Triangle);
+    }
+
+    let options = TextureOptions {
+        wrap_mode: match config.mode {
+            ScaleMode::Tile => TextureWrapMode::Repeat,
+            _ => TextureWrapMode::ClampToEdge,
+        },
+        ..TextureOptions::LINEAR
+    };
+
+    let blurred = (config.blur > 0.0).then(|| {
+        let small = if image.width() > BLUR_SIZE || image.height() > BLUR_SIZE {
+            image.resize(BLUR_SIZE, BLUR_SIZE, FilterType::Triangle)
+        } else {
+            image.clone()
+        };
+
+        let scale = small.width() as f32 / image.width() as f32;
+        let blurred = image::imageops::fast_blur(&small.to_rgba8(), config.blur * scale);
+
+        ctx.load_texture("background blurred", color_image(&blurred), options)
+    });
+
+    let texture = ctx.load_texture("background", color_image(&image.to_rgba8()), options);
+
+    Ok(BackgroundImage {
+        texture,
+        blurred,
+        mode: config.mode,
+    })
+}
+
+impl BackgroundImage {
+    /// where on the screen the image goes, and which part of the texture is shown there
+    fn placement(&self, screen: Rect, pixels_per_point: f32) -> (Rect, Rect) {
+        let size = self.texture.size_vec2();
+        let full = Rect::from_min_max(pos2(0.0, 0.0), pos2(1.0, 1.0));
+
+        match self.mode {
+            ScaleMode::Fill => {
+                let scale = (screen.width() / size.x).max(screen.height() / size.y);
+                (Rect::from_center_size(screen.center(), size * scale), full)
+            }
+            ScaleMode::Fit => {
+                let scale = (screen.width() / size.x).min(screen.height() / size.y);
+                (Rect::from_center_size(screen.center(), size * scale), full)
+            }
+            ScaleMode::Stretch => (screen, full),
+            ScaleMode::Center => (
+                Rect::from_center_size(screen.center(), size / pixels_per_point),
+                full,
+            ),
+            ScaleMode::Tile => (
+                screen,
+                Rect::from_min_size(pos2(0.0, 0.0), screen.size() * pixels_per_point / size),
+            ),
+        }
+    }
+}
+
+impl Background {
+    pub fn load(config: &BackgroundConfig, ctx: &egui::Context) -> Background {
+        let image = config
+            .image
+            .as_ref()
+            .and_then(|path| match load_image(path, config, ctx) {
+                Ok(image) => Some(image),
+                Err(err) => {
+                    log::warn!("failed to load background {}: {err:#}", path.display());
+                    None
+                }
+            });
+
+        Background {
+            image,
+            dim: config.dim.clamp(0.0, 1.0),
+        }
+    }
+
+    fn dim_color(&self) -> Color32 {
+        Color32::from_black_alpha((self.dim * 255.0) as u8)
+    }
+
+    /// paint the background over one output's `screen`, on top of the theme background colour
+    pub fn paint(&self, ctx: &egui::Context, screen: Rect) {
+        let painter = ctx.layer_painter(LayerId::background());
+
+        if let Some(image) = &self.image {
+            let (rect, uv) = image.placement(screen, ctx.pixels_per_point());
+            painter.image(image.texture.id(), rect, uv, Color32::WHITE);
+        }
+
+        if self.dim > 0.0 {
+            painter.rect_filled(screen, 0.0, self.dim_color());
+        }
+    }
+
+    /// paint the blurred background in `region` of `screen`, so it shows through a translucent
+    /// card drawn on top of it
+    pub fn paint_behind(
+        &self,
+        ctx: &egui::Context,
+        screen: Rect,
+        region: Rect,
+        corner_radius: f32,
+    ) {
+        let Some(image) = &self.image else {
+            return;
+        };
+
+        let Some(blurred) = &image.blurred else {
+            return;
+        };
+
+        let (rect, uv) = image.placement(screen, ctx.pixels_per_point());
+
+        let to_uv = |pos: egui::Pos2| uv.lerp_inside((pos - rect.min) / rect.size());
+        let region_uv = Rect::from_min_max(to_uv(region.min), to_uv(region.max));
+
+        let painter = ctx.layer_painter(LayerId::background());
+
+        painter.add(
+            epaint::RectShape::filled(region, corner_radius, Color32::WHITE)
+                .with_texture(blurred.id(), region_uv),
+        );
+
+        if self.dim > 0.0 {
+            painter.rect_filled(region, corner_radius, self.dim_color());
+        }
+    }
+}
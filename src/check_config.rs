@@ -0,0 +1,189 @@
+use std::{env, fs, os::unix::fs::PermissionsExt, path::Path};
+
+use anyhow::{Result, anyhow};
+use serde::Deserialize;
+
+use crate::{
+    fonts::resolve_font,
+    keyboard::check_layout,
+    sessions::get_sessions,
+    settings::{Settings, config_files, merge_tables, read_config_file},
+    time_area::ZONEINFO,
+};
+
+fn is_executable(path: &Path) -> bool {
+    fs::metadata(path).is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+/// whether the program the command starts with can be found, either as a path or on the PATH
+fn command_exists(command: &str) -> bool {
+    let Some(program) = command.split_whitespace().next() else {
+        return false;
+    };
+
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+
+    env::var_os("PATH")
+        .is_some_and(|paths| env::split_paths(&paths).any(|dir| is_executable(&dir.join(program))))
+}
+
+/// check a single file on its own, so errors can point at a line and column in it
+fn check_file(path: &Path, problems: &mut Vec<String>) -> Option<toml::Table> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(err) => {
+            problems.push(format!("{}: failed to read: {err}", path.display()));
+            return None;
+        }
+    };
+
+    let mut unknown = Vec::new();
+    let on_unknown = |key: serde_ignored::Path| unknown.push(key.to_string());
+
+    let result: Result<Settings, String> = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_ignored::deserialize(
+            &mut serde_json::Deserializer::from_str(&contents),
+            on_unknown,
+        )
+        .map_err(|err| err.to_string())
+    } else {
+        serde_ignored::deserialize(toml::Deserializer::new(&contents), on_unknown)
+            .map_err(|err| err.to_string())
+    };
+
+    for key in unknown {
+        problems.push(format!("{}: unknown key `{key}`", path.display()));
+    }
+
+    match result {
+        Ok(_) => read_config_file(path).ok(),
+        Err(err) => {
+            problems.push(format!("{}: {}", path.display(), err.trim_end()));
+            None
+        }
+    }
+}
+
+fn check_settings(settings: &Settings, problems: &mut Vec<String>) {
+    if let Some(command) = &settings.default_session_command
+        && !command_exists(command)
+    {
+        problems.push(format!(
+            "default_session_command: `{command}` was not found on the PATH"
+        ));
+    }
+
+    // the sessions from desktop files, the default session was checked above
+    for (path, (name, command)) in get_sessions(None) {
+        if let Some(path) = path
+            && !command_exists(&command)
+        {
+            problems.push(format!(
+                "{}: session `{name}` runs `{}` which was not found on the PATH",
+                path.display(),
+                command.trim_end()
+            ));
+        }
+    }
+
+    let fonts = &settings.fonts;
+
+    for (key, font) in [
+        ("fonts.proportional", &fonts.proportional),
+        ("fonts.monospace", &fonts.monospace),
+        ("fonts.icons", &fonts.icons),
+    ] {
+        if let Some(font) = font
+            && let Err(err) = resolve_font(font)
+        {
+            problems.push(format!("{key}: {err}"));
+        }
+    }
+
+    if let Some(image) = &settings.background.image
+        && !image.is_file()
+    {
+        problems.push(format!(
+            "background.image: `{}` does not exist",
+            image.display()
+        ));
+    }
+
+    for (index, output) in settings.outputs.iter().enumerate() {
+        if settings.outputs[..index]
+            .iter()
+            .any(|other| other.name == output.name)
+        {
+            problems.push(format!("outputs: `{}` is listed twice", output.name));
+        }
+    }
+
+    for layout in &settings.keyboard.layouts {
+        if let Err(err) = check_layout(layout) {
+            problems.push(format!("keyboard.layouts: {err:#}"));
+        }
+    }
+
+    if let Some(timezone) = &settings.clock.timezone
+        && !Path::new(ZONEINFO).join(timezone).is_file()
+    {
+        problems.push(format!("clock.timezone: `{timezone}` is not in {ZONEINFO}"));
+    }
+
+    if settings.scale.reference_height <= 0.0 {
+        problems.push("scale.reference_height: must be more than 0".to_string());
+    }
+
+    if settings.scale.factor <= 0.0 {
+        problems.push("scale.factor: must be more than 0".to_string());
+    }
+
+    if let Some(primary) = &settings.primary_output
+        && !settings
+            .outputs
+            .iter()
+            .any(|output| &output.name == primary)
+    {
+        problems.push(format!(
+            "primary_output: `{primary}` is not one of the outputs"
+        ));
+    }
+}
+
+/// check the config the greeter would load, printing every problem found. fails if there were any
+pub fn check_config(config: Option<&Path>) -> Result<()> {
+    let files = config_files(config)?;
+
+    if files.is_empty() {
+        println!("no config files found, the defaults will be used");
+    }
+
+    let mut problems = Vec::new();
+    let mut merged = toml::Table::new();
+
+    for file in &files {
+        println!("checking {}", file.display());
+
+        if let Some(table) = check_file(file, &mut problems) {
+            merge_tables(&mut merged, table);
+        }
+    }
+
+    match Settings::deserialize(merged) {
+        Ok(settings) => check_settings(&settings, &mut problems),
+        Err(err) => problems.push(format!("merged config: {err}")),
+    }
+
+    if problems.is_empty() {
+        println!("config ok");
+        return Ok(());
+    }
+
+    for problem in &problems {
+        eprintln!("{problem}");
+    }
+
+    Err(anyhow!("found {} problem(s) in the config", problems.len()))
+}
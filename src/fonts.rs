@@ -0,0 +1,148 @@
+use std::{fs, path::PathBuf, process::Command};
+
+use anyhow::{Context, Result, anyhow};
+use egui::{
+    FontData, FontFamily,
+    epaint::text::{FontInsert, FontPriority, InsertFontFamily},
+};
+use serde::{Deserialize, Serialize};
+
+/// the family the power buttons are drawn with, falls back to the embedded font
+pub const ICONS: &str = "icons";
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct FontSizes {
+    pub clock: f32,
+    pub date: f32,
+    /// everything in the login card
+    pub inputs: f32,
+    pub power_icons: f32,
+}
+
+impl Default for FontSizes {
+    fn default() -> Self {
+        Self {
+            clock: 144.0,
+            date: 48.0,
+            inputs: 18.0,
+            power_icons: 64.0,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TextFamily {
+    #[default]
+    Monospace,
+    Proportional,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct FontConfig {
+    /// a path to a font file, or a family name looked up with fontconfig
+    pub proportional: Option<String>,
+    pub monospace: Option<String>,
+    /// used for the power buttons, needs to have the nerd font glyphs
+    pub icons: Option<String>,
+    /// which of the fonts all the text is drawn with
+    pub text: TextFamily,
+    pub sizes: FontSizes,
+}
+
+/// find the file for a font, either given directly as a path or as a family name for fc-match
+pub fn resolve_font(font: &str) -> Result<PathBuf> {
+    if font.contains('/') {
+        let path = PathBuf::from(font);
+
+        if !path.is_file() {
+            return Err(anyhow!("font file {font} does not exist"));
+        }
+
+        return Ok(path);
+    }
+
+    let output = Command::new("fc-match")
+        .arg("--format=%{family}\n%{file}")
+        .arg(font)
+        .output()
+        .with_context(|| "failed to run fc-match")?;
+
+    let output = String::from_utf8_lossy(&output.stdout);
+    let (families, file) = output
+        .split_once('\n')
+        .ok_or_else(|| anyhow!("no font found for {font}"))?;
+
+    // fc-match always returns some font, so check that it is actually the one asked for
+    if !families
+        .split(',')
+        .any(|family| family.eq_ignore_ascii_case(font))
+    {
+        return Err(anyhow!(
+            "no font found for {font}, closest match is {families}"
+        ));
+    }
+
+    Ok(PathBuf::from(file))
+}
+
+fn add_configured_font(ctx: &egui::Context, font: &Option<String>, family: FontFamily) {
+    let Some(font) = font else {
+        return;
+    };
+
+    let data = resolve_font(font).and_then(|path| {
+        fs::read(&path).with_context(|| format!("failed to read {}", path.display()))
+    });
+
+    match data {
+        Ok(data) => ctx.add_font(FontInsert::new(
+            &format!("{family} {font}"),
+            FontData::from_owned(data),
+            vec![InsertFontFamily {
+                family,
+                priority: FontPriority::Highest,
+            }],
+        )),
+        Err(err) => log::warn!("using the embedded font instead of {font}: {err:#}"),
+    }
+}
+
+impl FontConfig {
+    pub fn apply(&self, ctx: &egui::Context) {
+        ctx.add_font(FontInsert::new(
+            "FiraCode",
+            FontData::from_static(include_bytes!("../fonts/FiraCodeNerdFontMono-Regular.ttf")),
+            vec![
+                InsertFontFamily {
+                    family: FontFamily::Monospace,
+                    priority: FontPriority::Highest,
+                },
+                InsertFontFamily {
+                    family: FontFamily::Proportional,
+                    priority: FontPriority::Lowest,
+                },
+                InsertFontFamily {
+                    family: FontFamily::Name(ICONS.into()),
+                    priority: FontPriority::Highest,
+                },
+            ],
+        ));
+
+        add_configured_font(ctx, &self.monospace, FontFamily::Monospace);
+        add_configured_font(ctx, &self.proportional, FontFamily::Proportional);
+        add_configured_font(ctx, &self.icons, FontFamily::Name(ICONS.into()));
+
+        ctx.style_mut(|style| {
+            style.override_font_id = Some(egui::FontId {
+                size: self.sizes.inputs,
+                family: match self.text {
+                    TextFamily::Monospace => FontFamily::Monospace,
+                    TextFamily::Proportional => FontFamily::Proportional,
+                },
+            });
+        });
+    }
+}
@@ -0,0 +1,81 @@
+use std::path::PathBuf;
+
+use gettextrs::{LocaleCategory, bind_textdomain_codeset, bindtextdomain, setlocale, textdomain};
+use serde::{Deserialize, Serialize};
+
+/// the name of the message catalogs, `<locale_dir>/<lang>/LC_MESSAGES/egui-greeter.mo`
+pub const DOMAIN: &str = "egui-greeter";
+
+/// languages written right to left, matched against the start of the locale name
+const RTL_LANGUAGES: [&str; 10] = ["ar", "ckb", "dv", "fa", "he", "ps", "sd", "ug", "ur", "yi"];
+
+fn default_locale_dir() -> PathBuf {
+    PathBuf::from(option_env!("LOCALEDIR").unwrap_or("/usr/share/locale"))
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TextDirection {
+    /// from the language of the locale
+    #[default]
+    Auto,
+    LeftToRight,
+    RightToLeft,
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+#[serde(default)]
+pub struct LocaleConfig {
+    /// a locale like `de_DE.UTF-8`, the one from `LANG` is used if not set
+    pub language: Option<String>,
+    /// where the message catalogs are installed
+    pub locale_dir: PathBuf,
+    pub direction: TextDirection,
+}
+
+impl Default for LocaleConfig {
+    fn default() -> Self {
+        Self {
+            language: None,
+            locale_dir: default_locale_dir(),
+            direction: TextDirection::default(),
+        }
+    }
+}
+
+fn is_rtl_locale(locale: &str) -> bool {
+    let language = locale.split(['_', '.', '@']).next().unwrap_or_default();
+
+    RTL_LANGUAGES.contains(&language)
+}
+
+impl LocaleConfig {
+    /// set the locale and load the translations. changes the locale of the whole process, so this
+    /// has to be called before any other threads are started. returns whether the layout should be
+    /// right to left
+    pub fn init(&self) -> bool {
+        let mut locale = self.language.as_deref().unwrap_or("");
+
+        if setlocale(LocaleCategory::LcAll, locale).is_none() {
+            log::warn!("locale {locale:?} is not available, using the one from LANG");
+            locale = "";
+            setlocale(LocaleCategory::LcAll, locale);
+        }
+
+        let result = bindtextdomain(DOMAIN, self.locale_dir.as_os_str())
+            .and_then(|_| bind_textdomain_codeset(DOMAIN, "UTF-8"))
+            .and_then(|_| textdomain(DOMAIN));
+
+        if let Err(err) = result {
+            log::warn!("failed to load the translations: {err}");
+        }
+
+        match self.direction {
+            // setting the same locale again returns its full name
+            TextDirection::Auto => setlocale(LocaleCategory::LcMessages, locale)
+                .is_some_and(|name| is_rtl_locale(&String::from_utf8_lossy(&name))),
+            TextDirection::LeftToRight => false,
+            TextDirection::RightToLeft => true,
+        }
+    }
+}
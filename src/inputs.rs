@@ -2,40 +2,171 @@ use std::{path::PathBuf, rc::Rc};
 
 use crate::{
     app::DisplayState,
-    auth_thread::{self, Handle, InputType},
-    search_selector::search_selector,
+    auth_thread::{self, AuthState, Handle, InputType, MessageKind},
+    fonts::ICONS,
+    keyboard::{layout_switcher, lock_warnings},
+    search_selector::{SelectorStyle, search_selector},
+    settings::UserEntry,
+    theme::Palette,
+    users::User,
 };
 use anyhow::Result;
-use egui::{Button, Color32, Frame, RichText, TextEdit, Ui, Vec2};
+use egui::{Button, FontFamily, Frame, Key, Label, RichText, Sense, Spinner, TextEdit, Ui, Vec2};
+use gettextrs::gettext;
 
-fn fancy_button(ui: &mut Ui, text: impl Into<String>) -> egui::Response {
+fn fancy_button(ui: &mut Ui, palette: &Palette, text: impl Into<String>) -> egui::Response {
     ui.add(
-        Button::new(RichText::new(text).color(Color32::from_rgb(36, 39, 58)))
+        Button::new(RichText::new(text).color(palette.on_accent))
             .frame(false)
-            .fill(Color32::from_rgb(183, 189, 248))
+            .fill(palette.accent)
             .corner_radius(10.0)
             .min_size(Vec2::new(220.0 + 10.0, 20.0 + 18.0)),
     )
 }
 
-pub fn text_edit_frame<F, A>(ui: &mut Ui, f: F) -> egui::InnerResponse<A>
+pub fn text_edit_frame<F, A>(ui: &mut Ui, palette: &Palette, f: F) -> egui::InnerResponse<A>
 where
     F: FnOnce(&mut Ui) -> A,
 {
     Frame::new()
         .inner_margin(10)
         .outer_margin(5)
-        .fill(Color32::from_rgb(36, 39, 58))
+        .fill(palette.input)
         .corner_radius(10.0)
         .show(ui, f)
 }
 
-pub fn basic_center_input(
+fn user_label(user: &User) -> String {
+    format!("{} ({})", user.display_name(), user.name)
+}
+
+/// tell the auth thread about a newly selected user, and switch to their preferences
+fn user_changed(
     state: &mut DisplayState,
     sessions: &[(Option<PathBuf>, (String, String))],
+    previous_user: Option<String>,
+    handle: &mut Handle,
+) -> Result<()> {
+    if state.user != previous_user
+        && let Some(user) = state.user.clone()
+    {
+        state.reset_attempt();
+        handle.send_command(auth_thread::Command::User(user))?;
+        state.waiting = true;
+        state.load_user_preferences(sessions);
+    }
+
+    Ok(())
+}
+
+pub fn user_input(
+    state: &mut DisplayState,
+    sessions: &[(Option<PathBuf>, (String, String))],
+    users: &[User],
+    user_entry: UserEntry,
+    palette: &Palette,
     handle: &mut Handle,
     ui: &mut Ui,
 ) -> Result<()> {
+    let previous_user = state.user.clone();
+
+    let changed = match user_entry {
+        UserEntry::Picker => search_selector(
+            ui.make_persistent_id("user_selector"),
+            &mut state.user_input,
+            &mut state.user,
+            users
+                .iter()
+                .map(|user| (Rc::new(user_label(user)), Some(user.name.clone()))),
+            &mut state.user_cache,
+            SelectorStyle {
+                width: 200.0,
+                palette,
+            },
+            ui,
+        ),
+        UserEntry::Text => {
+            let enter = text_edit_frame(ui, palette, |ui| {
+                let response = ui.add(
+                    TextEdit::singleline(&mut state.user_input)
+                        .hint_text(gettext("username"))
+                        .text_color(palette.selector_text)
+                        .desired_width(200.0)
+                        .frame(false),
+                );
+
+                // clicking or tabbing away from a half typed name doesn't pick it
+                response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter))
+            })
+            .inner;
+
+            let name = state.user_input.trim();
+
+            if enter && !name.is_empty() {
+                state.user = Some(name.to_string());
+            }
+
+            enter
+        }
+    };
+
+    if changed {
+        user_changed(state, sessions, previous_user, handle)?;
+    }
+
+    Ok(())
+}
+
+/// every user as a button, for when the user list is placed on its own. with text entry this is
+/// the same as the input in the login card
+pub fn user_list(
+    state: &mut DisplayState,
+    sessions: &[(Option<PathBuf>, (String, String))],
+    users: &[User],
+    user_entry: UserEntry,
+    palette: &Palette,
+    handle: &mut Handle,
+    ui: &mut Ui,
+) -> Result<()> {
+    if user_entry == UserEntry::Text {
+        return user_input(state, sessions, users, user_entry, palette, handle, ui);
+    }
+
+    let previous_user = state.user.clone();
+
+    for user in users {
+        let selected = state.user.as_deref() == Some(user.name.as_str());
+
+        let (text, fill) = match selected {
+            true => (palette.on_accent, palette.accent),
+            false => (palette.selector_text, palette.input),
+        };
+
+        let clicked = ui
+            .add(
+                Button::new(RichText::new(user_label(user)).color(text))
+                    .frame(false)
+                    .fill(fill)
+                    .corner_radius(10.0)
+                    .min_size(Vec2::new(220.0 + 10.0, 20.0 + 18.0)),
+            )
+            .clicked();
+
+        if clicked {
+            state.user = Some(user.name.clone());
+            state.user_input = user_label(user);
+        }
+    }
+
+    user_changed(state, sessions, previous_user, handle)
+}
+
+pub fn session_input(
+    state: &mut DisplayState,
+    sessions: &[(Option<PathBuf>, (String, String))],
+    palette: &Palette,
+    ui: &mut Ui,
+) {
     search_selector(
         ui.make_persistent_id("session_selector"),
         &mut state.session_input,
@@ -44,67 +175,210 @@ pub fn basic_center_input(
             (Rc::new(name.to_string()), (name.clone(), command.clone()))
         }),
         &mut state.search_cache,
-        200.0,
+        SelectorStyle {
+            width: 200.0,
+            palette,
+        },
         ui,
     );
+}
+
+/// the eye button that shows the typed password, returns whether it was clicked
+fn reveal_toggle(reveal: &mut bool, palette: &Palette, ui: &mut Ui) -> bool {
+    let icon = if *reveal { "\u{f070}" } else { "\u{f06e}" };
+
+    let clicked = ui
+        .add(
+            Label::new(
+                RichText::new(icon)
+                    .family(FontFamily::Name(ICONS.into()))
+                    .color(palette.input_text),
+            )
+            .sense(Sense::click()),
+        )
+        .on_hover_text(match *reveal {
+            true => gettext("hide password"),
+            false => gettext("show password"),
+        })
+        .clicked();
+
+    if clicked {
+        *reveal = !*reveal;
+    }
+
+    clicked
+}
 
-    if let Some(i) = &state.input_type {
+/// shown in the login card while greetd can't be reached
+pub fn unavailable_message(reason: &str, retrying: bool, palette: &Palette, ui: &mut Ui) {
+    ui.label(
+        RichText::new(gettext("greetd unavailable"))
+            .color(palette.error)
+            .heading(),
+    );
+
+    ui.label(RichText::new(reason).color(palette.text));
+
+    if retrying {
+        ui.horizontal(|ui| {
+            ui.add(Spinner::new().color(palette.accent));
+            ui.label(RichText::new(gettext("reconnecting…")).color(palette.text));
+        });
+    }
+}
+
+/// shown in the login card after the login broke, returns whether it should be restarted
+pub fn failure_message(err: &str, palette: &Palette, ui: &mut Ui) -> bool {
+    ui.label(
+        RichText::new(gettext("something went wrong"))
+            .color(palette.error)
+            .heading(),
+    );
+
+    ui.label(RichText::new(err).color(palette.text));
+
+    ui.add_space(5.0);
+
+    fancy_button(ui, palette, gettext("restart login")).clicked()
+}
+
+/// the messages from pam so far in this attempt, with the earlier ones faded
+fn conversation(state: &DisplayState, palette: &Palette, ui: &mut Ui) {
+    if state.conversation.len() > 1 {
+        ui.label(
+            RichText::new(gettext("step {}").replace("{}", &state.conversation.len().to_string()))
+                .color(palette.text)
+                .small(),
+        );
+    }
+
+    let current = state.conversation.len().saturating_sub(1);
+
+    for (index, (kind, message)) in state.conversation.iter().enumerate() {
+        let message = message.trim();
+
+        if message.is_empty() {
+            continue;
+        }
+
+        let color = match kind {
+            MessageKind::Error => palette.error,
+            MessageKind::Prompt | MessageKind::Info => palette.text,
+        };
+
+        let color = match index == current {
+            true => color,
+            false => color.gamma_multiply(0.5),
+        };
+
+        ui.label(RichText::new(message).color(color));
+    }
+}
+
+/// the input for the current prompt from greetd
+pub fn basic_center_input(
+    state: &mut DisplayState,
+    palette: &Palette,
+    allow_reveal: bool,
+    handle: &mut Handle,
+    ui: &mut Ui,
+) -> Result<()> {
+    conversation(state, palette, ui);
+
+    // also while greetd works on the first prompt or the session
+    let busy = state.waiting
+        || (state.user.is_some() && matches!(state.auth, AuthState::Idle | AuthState::Starting));
+
+    if busy {
+        ui.horizontal(|ui| {
+            ui.add(Spinner::new().color(palette.accent));
+            ui.label(RichText::new(gettext("waiting for greetd…")).color(palette.text));
+        });
+    }
+
+    let input = match &state.auth {
+        AuthState::Prompting { input, .. } if !state.waiting => Some(*input),
+        _ => None,
+    };
+
+    if let Some(i) = &input {
         match i {
             InputType::None => {
-                if ui.button("next").clicked() {
-                    state.input_type = None;
-                    handle.send_command(auth_thread::Command::Next)?
+                if ui.button(gettext("next")).clicked() {
+                    handle.send_command(auth_thread::Command::Next)?;
+                    state.waiting = true;
                 }
             }
-            InputType::Password => {
-                let mut enter = text_edit_frame(ui, |ui| {
-                    ui.add(
-                        TextEdit::singleline(&mut state.input)
-                            .password(true)
-                            .text_color(Color32::from_rgb(198, 160, 246))
-                            .desired_width(200.0)
-                            .frame(false),
-                    )
-                    .lost_focus()
-                })
-                .inner;
+            InputType::Password | InputType::Visible => {
+                let mut enter = ui
+                    .horizontal(|ui| {
+                        let password = matches!(i, InputType::Password);
+                        let toggle = password && allow_reveal;
 
-                ui.add_space(5.0);
+                        let enter = text_edit_frame(ui, palette, |ui| {
+                            ui.horizontal(|ui| {
+                                let response = ui.add(
+                                    TextEdit::singleline(&mut state.input)
+                                        .password(password && !state.reveal_password)
+                                        .text_color(palette.input_text)
+                                        .desired_width(if toggle { 176.0 } else { 200.0 })
+                                        .frame(false),
+                                );
 
-                enter |= fancy_button(ui, "submit").clicked();
+                                if toggle && reveal_toggle(&mut state.reveal_password, palette, ui)
+                                {
+                                    response.request_focus();
+                                }
 
-                if enter {
-                    state.input_type = None;
-                    handle.send_command(auth_thread::Command::Entered(std::mem::take(
-                        &mut state.input,
-                    )))?
-                }
-            }
-            InputType::Visible => {
-                let mut enter = text_edit_frame(ui, |ui| {
-                    ui.add(
-                        TextEdit::singleline(&mut state.input)
-                            .text_color(Color32::from_rgb(198, 160, 246))
-                            .desired_width(200.0)
-                            .frame(false),
-                    )
-                    .lost_focus()
-                })
-                .inner;
+                                // clicking the toggle also takes the focus away
+                                response.lost_focus() && ui.input(|i| i.key_pressed(Key::Enter))
+                            })
+                            .inner
+                        })
+                        .inner;
+
+                        layout_switcher(&mut state.keyboard, palette, ui);
+
+                        enter
+                    })
+                    .inner;
+
+                lock_warnings(&state.keyboard, palette, ui);
 
                 ui.add_space(5.0);
 
-                enter |= fancy_button(ui, "submit").clicked();
+                enter |= fancy_button(ui, palette, gettext("submit")).clicked();
 
                 if enter {
-                    state.input_type = None;
                     handle.send_command(auth_thread::Command::Entered(std::mem::take(
                         &mut state.input,
-                    )))?
+                    )))?;
+                    state.waiting = true;
                 }
             }
         }
     }
 
+    cancel_button(state, handle, ui)
+}
+
+/// give up on the attempt in progress and start over, also with escape
+fn cancel_button(state: &mut DisplayState, handle: &mut Handle, ui: &mut Ui) -> Result<()> {
+    let in_progress =
+        !state.conversation.is_empty() && state.auth.check(&auth_thread::Command::Cancel).is_ok();
+
+    if !in_progress {
+        return Ok(());
+    }
+
+    // a selector that is drawn later takes the escape to close its popup
+    let escape = !ui.memory(|mem| mem.any_popup_open()) && ui.input(|i| i.key_pressed(Key::Escape));
+
+    if ui.button(gettext("cancel")).clicked() || escape {
+        handle.send_command(auth_thread::Command::Cancel)?;
+        state.reset_attempt();
+        state.waiting = true;
+    }
+
     Ok(())
 }
@@ -0,0 +1,471 @@
+use std::{
+    ffi::{CString, c_char},
+    fs, ptr,
+    time::{Duration, Instant},
+};
+
+use anyhow::{Result, anyhow};
+use egui::{Button, Event, Key, RichText, Ui, Vec2};
+use serde::{Deserialize, Serialize};
+use xkbcommon_dl::{
+    XKB_MOD_INVALID, XKB_MOD_NAME_CAPS, XKB_MOD_NAME_SHIFT, xkb_context_flags, xkb_keymap,
+    xkb_keymap_compile_flags, xkb_rule_names, xkb_state, xkbcommon_option,
+};
+
+use crate::theme::Palette;
+
+/// where the kernel lists the keyboard leds, as `input<n>::capslock` and so on
+const LEDS: &str = "/sys/class/leds";
+
+/// how long the leds read from sysfs are trusted before reading them again
+const LED_REFRESH: Duration = Duration::from_millis(300);
+
+/// xkb keycodes are the evdev ones offset by 8
+const EVDEV_OFFSET: u32 = 8;
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct KeyboardConfig {
+    /// xkb layouts to switch between, like `us`, `de` or `us(dvorak)`. the first one is used until
+    /// another is picked
+    pub layouts: Vec<String>,
+    /// make the keypad type numbers in the greeter even when num lock is off. a wayland client
+    /// can't turn on the compositor's num lock itself
+    pub force_numlock: bool,
+}
+
+/// the evdev keycode for the key in this position on a us keyboard
+fn evdev_keycode(key: Key) -> Option<u32> {
+    Some(match key {
+        Key::Backtick => 41,
+        Key::Num1 => 2,
+        Key::Num2 => 3,
+        Key::Num3 => 4,
+        Key::Num4 => 5,
+        Key::Num5 => 6,
+        Key::Num6 => 7,
+        Key::Num7 => 8,
+        Key::Num8 => 9,
+        Key::Num9 => 10,
+        Key::Num0 => 11,
+        Key::Minus => 12,
+        Key::Equals => 13,
+        Key::Q => 16,
+        Key::W => 17,
+        Key::E => 18,
+        Key::R => 19,
+        Key::T => 20,
+        Key::Y => 21,
+        Key::U => 22,
+        Key::I => 23,
+        Key::O => 24,
+        Key::P => 25,
+        Key::OpenBracket => 26,
+        Key::CloseBracket => 27,
+        Key::A => 30,
+        Key::S => 31,
+        Key::D => 32,
+        Key::F => 33,
+        Key::G => 34,
+        Key::H => 35,
+        Key::J => 36,
+        Key::K => 37,
+        Key::L => 38,
+        Key::Semicolon => 39,
+        Key::Quote => 40,
+        Key::Backslash => 43,
+        Key::Z => 44,
+        Key::X => 45,
+        Key::C => 46,
+        Key::V => 47,
+        Key::B => 48,
+        Key::N => 49,
+        Key::M => 50,
+        Key::Comma => 51,
+        Key::Period => 52,
+        Key::Slash => 53,
+        _ => return None,
+    })
+}
+
+/// split `us(dvorak)` into the layout and variant
+fn layout_and_variant(name: &str) -> (&str, &str) {
+    match name.split_once('(') {
+        Some((layout, variant)) => (layout, variant.trim_end_matches(')')),
+        None => (name, ""),
+    }
+}
+
+/// a compiled xkb keymap, used to work out what a key types in a layout the compositor isn't using
+struct Keymap {
+    keymap: *mut xkb_keymap,
+    state: *mut xkb_state,
+    shift: u32,
+    caps: u32,
+}
+
+impl Keymap {
+    fn new(name: &str) -> Result<Keymap> {
+        let xkb = xkbcommon_option().ok_or_else(|| anyhow!("libxkbcommon could not be loaded"))?;
+
+        let (layout, variant) = layout_and_variant(name);
+        let layout = CString::new(layout)?;
+        let variant = CString::new(variant)?;
+
+        let names = xkb_rule_names {
+            rules: ptr::null(),
+            model: ptr::null(),
+            layout: layout.as_ptr(),
+            variant: variant.as_ptr(),
+            options: ptr::null(),
+        };
+
+        // SAFETY: the context is only used here and freed before returning, the keymap and state
+        // are owned by the Keymap and freed when it is dropped
+        unsafe {
+            let context = (xkb.xkb_context_new)(xkb_context_flags::XKB_CONTEXT_NO_FLAGS);
+
+            if context.is_null() {
+                return Err(anyhow!("failed to create an xkb context"));
+            }
+
+            let keymap = (xkb.xkb_keymap_new_from_names)(
+                context,
+                &names,
+                xkb_keymap_compile_flags::XKB_KEYMAP_COMPILE_NO_FLAGS,
+            );
+
+            (xkb.xkb_context_unref)(context);
+
+            if keymap.is_null() {
+                return Err(anyhow!("unknown keyboard layout {name}"));
+            }
+
+            let mod_mask = |name: &[u8]| match (xkb.xkb_keymap_mod_get_index)(
+                keymap,
+                name.as_ptr() as *const c_char,
+            ) {
+                XKB_MOD_INVALID => 0,
+                index => 1 << index,
+            };
+
+            let shift = mod_mask(XKB_MOD_NAME_SHIFT);
+            let caps = mod_mask(XKB_MOD_NAME_CAPS);
+
+            let state = (xkb.xkb_state_new)(keymap);
+
+            if state.is_null() {
+                (xkb.xkb_keymap_unref)(keymap);
+                return Err(anyhow!("failed to create the xkb state for {name}"));
+            }
+
+            Ok(Keymap {
+                keymap,
+                state,
+                shift,
+                caps,
+            })
+        }
+    }
+
+    /// the text typed by the key at the us position `key`
+    fn text(&self, key: Key, shift: bool, caps_lock: bool) -> Option<String> {
+        let keycode = evdev_keycode(key)? + EVDEV_OFFSET;
+        let xkb = xkbcommon_option()?;
+
+        let mut buffer = [0u8; 16];
+
+        // SAFETY: the state is valid for the lifetime of self, and the buffer length is passed
+        let written = unsafe {
+            (xkb.xkb_state_update_mask)(
+                self.state,
+                if shift { self.shift } else { 0 },
+                0,
+                if caps_lock { self.caps } else { 0 },
+                0,
+                0,
+                0,
+            );
+
+            (xkb.xkb_state_key_get_utf8)(
+                self.state,
+                keycode,
+                buffer.as_mut_ptr().cast(),
+                buffer.len(),
+            )
+        };
+
+        let written = usize::try_from(written).ok()?.min(buffer.len() - 1);
+
+        (written > 0).then(|| String::from_utf8_lossy(&buffer[..written]).into_owned())
+    }
+}
+
+impl Drop for Keymap {
+    fn drop(&mut self) {
+        if let Some(xkb) = xkbcommon_option() {
+            // SAFETY: both were created in Keymap::new and aren't used after this
+            unsafe {
+                (xkb.xkb_state_unref)(self.state);
+                (xkb.xkb_keymap_unref)(self.keymap);
+            }
+        }
+    }
+}
+
+/// whether xkb can compile the layout
+pub fn check_layout(name: &str) -> Result<()> {
+    Keymap::new(name).map(|_| ())
+}
+
+/// the layouts that can be picked, and the one typed text is translated to
+pub struct Keyboard {
+    layouts: Vec<(String, Option<Keymap>)>,
+    active: usize,
+    force_numlock: bool,
+    caps_lock_guess: Option<bool>,
+    leds: Leds,
+}
+
+/// the lock leds, cached so sysfs isn't read for every frame
+struct Leds {
+    caps_lock: Option<bool>,
+    num_lock: Option<bool>,
+    read_at: Instant,
+}
+
+impl Leds {
+    fn read() -> Leds {
+        Leds {
+            caps_lock: led_state("capslock"),
+            num_lock: led_state("numlock"),
+            read_at: Instant::now(),
+        }
+    }
+}
+
+impl Keyboard {
+    pub fn new(config: &KeyboardConfig) -> Keyboard {
+        let layouts = config
+            .layouts
+            .iter()
+            .map(|name| {
+                let keymap = Keymap::new(name)
+                    .inspect_err(|err| log::warn!("can't switch to layout {name}: {err:#}"))
+                    .ok();
+
+                (name.clone(), keymap)
+            })
+            .collect();
+
+        Keyboard {
+            layouts,
+            active: 0,
+            force_numlock: config.force_numlock,
+            caps_lock_guess: None,
+            leds: Leds::read(),
+        }
+    }
+
+    pub fn active(&self) -> Option<&str> {
+        self.layouts.get(self.active).map(|(name, _)| name.as_str())
+    }
+
+    /// switch to a layout by name, does nothing if it isn't one of the configured layouts
+    pub fn select(&mut self, name: &str) {
+        if let Some(index) = self.layouts.iter().position(|(n, _)| n == name) {
+            self.active = index;
+        }
+    }
+
+    fn cycle(&mut self) {
+        if !self.layouts.is_empty() {
+            self.active = (self.active + 1) % self.layouts.len();
+        }
+    }
+
+    /// whether caps lock is on, from the keyboard leds or otherwise what was last typed
+    pub fn caps_lock(&self) -> Option<bool> {
+        self.leds.caps_lock.or(self.caps_lock_guess)
+    }
+
+    /// whether num lock is off and the keypad won't type numbers
+    pub fn num_lock_off(&self) -> bool {
+        !self.force_numlock && self.leds.num_lock == Some(false)
+    }
+
+    /// read the leds again when they are old or a key was pressed, which may have toggled a lock
+    fn refresh_leds(&mut self, ctx: &egui::Context) {
+        let key_pressed = ctx.input(|input| {
+            input
+                .events
+                .iter()
+                .any(|event| matches!(event, Event::Key { pressed: true, .. }))
+        });
+
+        if key_pressed || self.leds.read_at.elapsed() >= LED_REFRESH {
+            self.leds = Leds::read();
+        }
+
+        // the compositor may only switch the led after this frame
+        if key_pressed {
+            ctx.request_repaint_after(LED_REFRESH);
+        }
+    }
+
+    /// replace the text the compositor typed with what the keys type in the active layout, and
+    /// make the keypad type numbers with `force_numlock`. has to be called before any widgets read
+    /// the input
+    pub fn process_input(&mut self, ctx: &egui::Context) {
+        self.refresh_leds(ctx);
+
+        if !ctx.wants_keyboard_input() {
+            return;
+        }
+
+        let keymap = self
+            .layouts
+            .get(self.active)
+            .and_then(|(_, keymap)| keymap.as_ref());
+
+        ctx.input_mut(|input| {
+            let mut events = std::mem::take(&mut input.events).into_iter().peekable();
+            let mut pressed = None;
+
+            while let Some(mut event) = events.next() {
+                match &mut event {
+                    Event::Key {
+                        physical_key: Some(key),
+                        pressed: true,
+                        modifiers,
+                        ..
+                    } => {
+                        let typing = !modifiers.ctrl && !modifiers.alt && !modifiers.command;
+
+                        // egui-winit sends the text for a key straight after the key event
+                        let has_text = matches!(events.peek(), Some(Event::Text(_)));
+
+                        pressed = (typing && has_text).then_some((*key, modifiers.shift));
+
+                        // the keypad sends navigation keys and no text when num lock is off
+                        if self.force_numlock
+                            && typing
+                            && !has_text
+                            && let Some(digit) = keypad_digit(*key)
+                        {
+                            event = Event::Text(digit.to_string());
+                        }
+                    }
+                    Event::Text(text) => {
+                        if let Some((key, shift)) = pressed.take() {
+                            let mut chars = text.chars();
+
+                            // a letter in the other case to shift means caps lock is on
+                            if let (Some(c), None) = (chars.next(), chars.next())
+                                && c.is_lowercase() != c.is_uppercase()
+                            {
+                                self.caps_lock_guess = Some(c.is_uppercase() != shift);
+                            }
+
+                            if let Some(keymap) = keymap
+                                && !from_keypad(key, text)
+                                && let Some(translated) =
+                                    keymap.text(key, shift, self.caps_lock_guess == Some(true))
+                            {
+                                *text = translated;
+                            }
+                        }
+                    }
+                    _ => {}
+                }
+
+                input.events.push(event);
+            }
+        });
+    }
+}
+
+/// whether the text may have come from the keypad, which egui reports as the main row key with
+/// the same symbol. that text is kept as the compositor typed it, so pins typed on the keypad work
+/// with any layout, but a main row key that types the same in the compositor's layout is also kept
+fn from_keypad(key: Key, text: &str) -> bool {
+    let keypad = match key {
+        Key::Minus => '-',
+        Key::Slash => '/',
+        key => match keypad_digit(key) {
+            Some(digit) => digit,
+            None => return false,
+        },
+    };
+
+    text.chars().eq([keypad])
+}
+
+/// the digit a key on the keypad types with num lock on, egui doesn't tell apart the keypad and
+/// the number row so this is only used for keys that didn't type anything
+fn keypad_digit(key: Key) -> Option<char> {
+    let digit = match key {
+        Key::Num0 => '0',
+        Key::Num1 => '1',
+        Key::Num2 => '2',
+        Key::Num3 => '3',
+        Key::Num4 => '4',
+        Key::Num5 => '5',
+        Key::Num6 => '6',
+        Key::Num7 => '7',
+        Key::Num8 => '8',
+        Key::Num9 => '9',
+        _ => return None,
+    };
+
+    Some(digit)
+}
+
+/// the state of a lock key from the keyboard leds in sysfs, on if any keyboard has it on. none if
+/// there aren't any keyboards with the led
+fn led_state(led: &str) -> Option<bool> {
+    let suffix = format!("::{led}");
+
+    fs::read_dir(LEDS)
+        .ok()?
+        .flatten()
+        .filter(|entry| entry.file_name().to_string_lossy().ends_with(&suffix))
+        .filter_map(|entry| fs::read_to_string(entry.path().join("brightness")).ok())
+        .map(|brightness| brightness.trim() != "0")
+        .reduce(|a, b| a || b)
+}
+
+/// the caps lock and num lock warnings shown under the password field
+pub fn lock_warnings(keyboard: &Keyboard, palette: &Palette, ui: &mut Ui) {
+    if keyboard.caps_lock() == Some(true) {
+        ui.label(RichText::new(gettextrs::gettext("caps lock is on")).color(palette.error));
+    }
+
+    if keyboard.num_lock_off() {
+        ui.label(RichText::new(gettextrs::gettext("num lock is off")).color(palette.text));
+    }
+}
+
+/// shows the active layout, clicking it switches to the next one. returns whether it was switched
+pub fn layout_switcher(keyboard: &mut Keyboard, palette: &Palette, ui: &mut Ui) -> bool {
+    let Some(active) = keyboard.active() else {
+        return false;
+    };
+
+    let clicked = ui
+        .add(
+            Button::new(RichText::new(active).color(palette.on_accent))
+                .frame(false)
+                .fill(palette.accent)
+                .corner_radius(10.0)
+                .min_size(Vec2::new(38.0, 38.0)),
+        )
+        .on_hover_text(gettextrs::gettext("keyboard layout"))
+        .clicked();
+
+    if clicked {
+        keyboard.cycle();
+    }
+
+    clicked
+}
@@ -0,0 +1,126 @@
+use std::fs;
+
+use egui::Align2;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    LeftTop,
+    CenterTop,
+    RightTop,
+    LeftCenter,
+    CenterCenter,
+    RightCenter,
+    LeftBottom,
+    CenterBottom,
+    RightBottom,
+}
+
+impl Anchor {
+    pub fn align(self) -> Align2 {
+        match self {
+            Anchor::LeftTop => Align2::LEFT_TOP,
+            Anchor::CenterTop => Align2::CENTER_TOP,
+            Anchor::RightTop => Align2::RIGHT_TOP,
+            Anchor::LeftCenter => Align2::LEFT_CENTER,
+            Anchor::CenterCenter => Align2::CENTER_CENTER,
+            Anchor::RightCenter => Align2::RIGHT_CENTER,
+            Anchor::LeftBottom => Align2::LEFT_BOTTOM,
+            Anchor::CenterBottom => Align2::CENTER_BOTTOM,
+            Anchor::RightBottom => Align2::RIGHT_BOTTOM,
+        }
+    }
+
+    /// the same anchor on the other side, for right to left languages
+    pub fn mirrored(self) -> Anchor {
+        match self {
+            Anchor::LeftTop => Anchor::RightTop,
+            Anchor::RightTop => Anchor::LeftTop,
+            Anchor::LeftCenter => Anchor::RightCenter,
+            Anchor::RightCenter => Anchor::LeftCenter,
+            Anchor::LeftBottom => Anchor::RightBottom,
+            Anchor::RightBottom => Anchor::LeftBottom,
+            Anchor::CenterTop | Anchor::CenterCenter | Anchor::CenterBottom => self,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, PartialEq, Debug)]
+#[serde(tag = "component", rename_all = "snake_case")]
+pub enum Component {
+    Clock,
+    /// the avatar and password input, and the user list and session selector if they aren't
+    /// placed on their own
+    LoginCard,
+    PowerMenu,
+    SessionSelector,
+    UserList,
+    Hostname {
+        size: Option<f32>,
+    },
+    Text {
+        text: String,
+        size: Option<f32>,
+    },
+}
+
+fn default_visible() -> bool {
+    true
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct LayoutEntry {
+    #[serde(flatten)]
+    pub component: Component,
+    pub anchor: Anchor,
+    #[serde(default)]
+    pub offset: [f32; 2],
+    #[serde(default = "default_visible")]
+    pub visible: bool,
+}
+
+/// where everything was before the layout was configurable
+pub fn default_layout() -> Vec<LayoutEntry> {
+    vec![
+        LayoutEntry {
+            component: Component::LoginCard,
+            anchor: Anchor::CenterCenter,
+            offset: [0.0, 0.0],
+            visible: true,
+        },
+        LayoutEntry {
+            component: Component::PowerMenu,
+            anchor: Anchor::RightBottom,
+            offset: [-20.0, 10.0],
+            visible: true,
+        },
+        LayoutEntry {
+            component: Component::Clock,
+            anchor: Anchor::LeftBottom,
+            offset: [20.0, 10.0],
+            visible: true,
+        },
+    ]
+}
+
+pub fn mirror_layout(layout: &mut [LayoutEntry]) {
+    for entry in layout {
+        entry.anchor = entry.anchor.mirrored();
+        entry.offset[0] = -entry.offset[0];
+    }
+}
+
+/// whether the layout shows the component on its own, instead of in the login card
+pub fn is_placed(layout: &[LayoutEntry], component: &Component) -> bool {
+    layout
+        .iter()
+        .any(|entry| entry.visible && entry.component == *component)
+}
+
+pub fn hostname() -> String {
+    fs::read_to_string("/proc/sys/kernel/hostname")
+        .or_else(|_| fs::read_to_string("/etc/hostname"))
+        .map(|name| name.trim().to_string())
+        .unwrap_or_default()
+}
@@ -1,28 +1,59 @@
+mod accounts_service;
 mod app;
 mod auth_thread;
+mod avatar;
+mod background;
+mod check_config;
+mod fonts;
+mod i18n;
 mod inputs;
+mod keyboard;
+mod layout;
+mod outputs;
 mod power_area;
+mod scale;
 mod search_selector;
 mod sessions;
 mod settings;
+mod state_cache;
+mod theme;
 mod time_area;
+mod users;
 
 use app::DisplayManager;
 
 use anyhow::anyhow;
 use clap::Parser;
 
-use crate::settings::{Args, Settings};
+use crate::{
+    settings::{Args, ArgsCommand, Settings},
+    state_cache::StateCache,
+};
 
 fn main() -> anyhow::Result<()> {
     env_logger::init();
 
     let args = Args::parse();
 
-    let settings = Settings::from_args(args)?;
+    if let Some(ArgsCommand::CheckConfig) = args.command {
+        return check_config::check_config(args.config.as_deref());
+    }
+
+    let mut settings = Settings::from_args(args)?;
+
+    let rtl = settings.locale.init();
+
+    settings.clock.init_timezone();
+
+    let cache = StateCache::load(&settings.state_file);
+
+    // a user from the arguments or the config always wins
+    if settings.user.is_none() {
+        settings.user = cache.last_user.clone();
+    }
 
     let options = eframe::NativeOptions {
-        viewport: egui::ViewportBuilder::default().with_inner_size([1440.0, 2560.0]),
+        viewport: outputs::root_viewport(&settings.outputs, settings.fullscreen),
         ..Default::default()
     };
 
@@ -30,7 +61,11 @@ fn main() -> anyhow::Result<()> {
         eframe::run_native(
             "rust display manager",
             options,
-            Box::new(|cc| Ok(Box::new(DisplayManager::new(settings, handle, cc)))),
+            Box::new(|cc| {
+                Ok(Box::new(DisplayManager::new(
+                    settings, cache, handle, rtl, cc,
+                )))
+            }),
         )
         .map_err(|err| anyhow!("{}", err.to_string()))
     })?;
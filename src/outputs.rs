@@ -0,0 +1,67 @@
+use egui::{Pos2, Rect, Vec2, ViewportBuilder};
+use serde::{Deserialize, Serialize};
+
+/// a monitor inside the greeter's window. egui can't list the monitors itself, so they have to be
+/// given in the config
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OutputConfig {
+    pub name: String,
+    /// the top left corner of the output in the compositor's layout
+    #[serde(default)]
+    pub position: [f32; 2],
+    pub size: [f32; 2],
+}
+
+impl OutputConfig {
+    /// where the output is in the compositor's layout
+    fn rect(&self) -> Rect {
+        Rect::from_min_size(Pos2::from(self.position), Vec2::from(self.size))
+    }
+}
+
+/// the part of the compositor's layout covered by the outputs
+fn layout_bounds(outputs: &[OutputConfig]) -> Option<Rect> {
+    outputs
+        .iter()
+        .map(OutputConfig::rect)
+        .reduce(|bounds, rect| bounds.union(rect))
+}
+
+/// the only viewport the greeter opens. wayland compositors place toplevels themselves, so one
+/// window is used for every output and the compositor has to span it over all of them (cage does
+/// with `-m extend`)
+pub fn root_viewport(outputs: &[OutputConfig], fullscreen: bool) -> ViewportBuilder {
+    let size = layout_bounds(outputs).map_or(Vec2::new(1440.0, 2560.0), |bounds| bounds.size());
+
+    ViewportBuilder::default()
+        .with_inner_size(size)
+        .with_fullscreen(fullscreen)
+}
+
+/// where each output is on `screen`, the whole screen if there are none. the layout is stretched
+/// over the screen, so this doesn't depend on the zoom or the compositor's scale
+pub fn output_rects(outputs: &[OutputConfig], screen: Rect) -> Vec<Rect> {
+    let Some(bounds) = layout_bounds(outputs) else {
+        return vec![screen];
+    };
+
+    let scale = screen.size() / bounds.size().max(Vec2::splat(1.0));
+
+    outputs
+        .iter()
+        .map(|output| {
+            let rect = output.rect();
+            Rect::from_min_size(
+                screen.min + (rect.min - bounds.min) * scale,
+                rect.size() * scale,
+            )
+        })
+        .collect()
+}
+
+/// which output the login card starts on, the configured primary or the first one
+pub fn primary_index(outputs: &[OutputConfig], primary: Option<&str>) -> usize {
+    primary
+        .and_then(|name| outputs.iter().position(|output| output.name == name))
+        .unwrap_or(0)
+}
@@ -1,29 +1,42 @@
 use std::process::Command;
 
 use anyhow::Result;
-use egui::{Color32, Label, Response, RichText, Sense, Ui};
+use egui::{FontFamily, Label, Response, RichText, Sense, Ui};
 
-fn power_button(name: &'static str, ui: &mut Ui) -> Response {
+use crate::{
+    fonts::{FontSizes, ICONS},
+    theme::Palette,
+};
+
+fn power_button(name: &'static str, palette: &Palette, sizes: &FontSizes, ui: &mut Ui) -> Response {
     ui.add(
         Label::new(
             RichText::new(name)
-                .size(64.0)
-                .color(Color32::from_rgb(139, 213, 202)),
+                .size(sizes.power_icons)
+                .family(FontFamily::Name(ICONS.into()))
+                .color(palette.icons),
         )
         .sense(Sense::click()),
     )
 }
 
-pub fn power_area(ui: &mut Ui) -> Result<()> {
-    ui.horizontal(|ui| -> Result<()> {
-        if power_button("", ui).clicked() {
-            Command::new("systemctl").arg("reboot").spawn()?;
-        }
+pub fn power_area(ui: &mut Ui, palette: &Palette, sizes: &FontSizes, rtl: bool) -> Result<()> {
+    let mut buttons = [("", "reboot"), ("⏻", "poweroff")];
 
-        ui.add_space(20.0);
+    // reboot stays on the reading start side
+    if rtl {
+        buttons.reverse();
+    }
+
+    ui.horizontal(|ui| -> Result<()> {
+        for (index, (icon, action)) in buttons.into_iter().enumerate() {
+            if index > 0 {
+                ui.add_space(20.0);
+            }
 
-        if power_button("⏻", ui).clicked() {
-            Command::new("systemctl").arg("poweroff").spawn()?;
+            if power_button(icon, palette, sizes, ui).clicked() {
+                Command::new("systemctl").arg(action).spawn()?;
+            }
         }
 
         Ok(())
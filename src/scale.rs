@@ -0,0 +1,45 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+#[serde(default)]
+pub struct ScaleConfig {
+    /// the screen height (in pixels) the sizes in the config are for, everything is scaled by the
+    /// actual height of the screen over this
+    pub reference_height: f32,
+    /// multiplied on top of the scale from the screen size
+    pub factor: f32,
+}
+
+impl Default for ScaleConfig {
+    fn default() -> Self {
+        Self {
+            reference_height: 1440.0,
+            factor: 1.0,
+        }
+    }
+}
+
+impl ScaleConfig {
+    /// scale everything to the size of `screen`, the output with the login card on it
+    pub fn apply(&self, ctx: &egui::Context, screen: egui::Rect) {
+        let height = screen.height() * ctx.pixels_per_point();
+
+        let Some(native) = ctx.native_pixels_per_point() else {
+            return;
+        };
+
+        if height <= 0.0 || self.reference_height <= 0.0 {
+            return;
+        }
+
+        // a broken factor would make the greeter unusable, check-config reports it
+        let factor = if self.factor > 0.0 { self.factor } else { 1.0 };
+
+        let zoom = height / self.reference_height * factor / native;
+
+        // changing the zoom repaints, so small differences from rounding are left alone
+        if (ctx.zoom_factor() - zoom).abs() > 0.01 {
+            ctx.set_zoom_factor(zoom);
+        }
+    }
+}
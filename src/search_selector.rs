@@ -1,8 +1,8 @@
 use std::{hash::Hash, rc::Rc};
 
-use egui::{Button, Color32, RichText, TextEdit, Ui, popup_below_widget};
+use egui::{Button, Key, Modifiers, RichText, TextEdit, Ui, popup_below_widget};
 
-use crate::inputs::text_edit_frame;
+use crate::{inputs::text_edit_frame, theme::Palette};
 
 #[derive(Debug, Default, Clone)]
 pub struct SingleCache<K, V> {
@@ -34,34 +34,42 @@ impl<K, V> SingleCache<K, V> {
     }
 }
 
-fn selector_button(ui: &mut Ui, text: &str) -> egui::Response {
+fn selector_button(ui: &mut Ui, palette: &Palette, text: &str) -> egui::Response {
     ui.add(
-        Button::new(RichText::new(text).color(Color32::from_rgb(245, 189, 230)))
+        Button::new(RichText::new(text).color(palette.selector_text))
             .frame(false)
-            .fill(Color32::from_rgb(36, 39, 58)),
+            .fill(palette.input),
     )
 }
 
 pub type SelectorCache<A> = SingleCache<String, Vec<(Rc<String>, A)>>;
 
+/// how a selector is drawn
+pub(crate) struct SelectorStyle<'a> {
+    pub width: f32,
+    pub palette: &'a Palette,
+}
+
 pub(crate) fn search_selector<A, I: Hash>(
     id: I,
     text: &mut String,
     selection: &mut A,
     options: impl Iterator<Item = (Rc<String>, A)>,
     cache: &mut SelectorCache<A>,
-    width: f32,
+    style: SelectorStyle,
     ui: &mut Ui,
 ) -> bool
 where
     A: Clone,
 {
-    let edit = text_edit_frame(ui, |ui| {
+    let SelectorStyle { width, palette } = style;
+
+    let edit = text_edit_frame(ui, palette, |ui| {
         ui.add(
             TextEdit::singleline(text)
                 .desired_width(width)
                 .frame(false)
-                .text_color(Color32::from_rgb(245, 189, 230)),
+                .text_color(palette.selector_text),
         )
     })
     .inner;
@@ -74,13 +82,23 @@ where
         ui.memory_mut(|mem| mem.open_popup(id));
     }
 
+    // escape only closes the selector, it shouldn't also cancel the login
+    let open = ui.memory(|mem| mem.is_popup_open(id));
+
+    let escape = (open || edit.has_focus() || edit.lost_focus())
+        && ui.input_mut(|i| i.consume_key(Modifiers::NONE, Key::Escape));
+
+    if escape && open {
+        ui.memory_mut(|mem| mem.close_popup());
+    }
+
     popup_below_widget(
         ui,
         id,
         &edit,
         egui::PopupCloseBehavior::CloseOnClickOutside,
         |ui| {
-            text_edit_frame(ui, |ui| {
+            text_edit_frame(ui, palette, |ui| {
                 let vals = cache.get(text, || {
                     options
                         .filter(|(name, _value)| name.contains(text.as_str()))
@@ -94,7 +112,7 @@ where
                 }
 
                 for (name, value) in vals {
-                    if selector_button(ui, name.as_str()).clicked() {
+                    if selector_button(ui, palette, name.as_str()).clicked() {
                         changed = true;
                         *selection = value.clone();
                         ui.memory_mut(|mem| mem.close_popup());
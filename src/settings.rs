@@ -1,11 +1,35 @@
 use anyhow::{Context, Result};
-use clap::Parser;
-use once_cell::sync::OnceCell;
+use clap::{Parser, Subcommand};
+
+use crate::{
+    background::BackgroundConfig,
+    fonts::FontConfig,
+    i18n::LocaleConfig,
+    keyboard::KeyboardConfig,
+    layout::{LayoutEntry, default_layout},
+    outputs::OutputConfig,
+    scale::ScaleConfig,
+    theme::ThemeConfig,
+    time_area::ClockConfig,
+};
 use serde::{Deserialize, Serialize};
-use std::{fs, path::PathBuf};
+use std::{
+    fs,
+    io::ErrorKind,
+    path::{Path, PathBuf},
+};
+
+#[derive(Subcommand, Debug)]
+pub enum ArgsCommand {
+    /// check the config for problems without starting the greeter, exits non-zero if there are any
+    CheckConfig,
+}
 
 #[derive(Parser, Debug)]
 pub struct Args {
+    #[command(subcommand)]
+    pub command: Option<ArgsCommand>,
+
     #[arg(short, long)]
     pub user: Option<String>,
 
@@ -15,62 +39,273 @@ pub struct Args {
     #[arg(short = 'C', long)]
     pub session_command: Option<String>,
 
-    #[arg(short, long)]
+    #[arg(short, long, global = true)]
     pub config: Option<PathBuf>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum UserEntry {
+    /// pick from the local users in /etc/passwd
+    #[default]
+    Picker,
+    /// type in the username, for users that are not in /etc/passwd (ldap, sssd, ...)
+    Text,
+}
+
 #[derive(Serialize, Deserialize)]
+#[serde(default)]
 pub struct Settings {
-    pub default_session_name: String,
-    pub default_session_command: String,
-    pub user: String,
+    /// defaults to the command if not set
+    pub default_session_name: Option<String>,
+    pub default_session_command: Option<String>,
+    pub user: Option<String>,
+    pub user_entry: UserEntry,
+    /// show a button to reveal the typed password
+    pub password_reveal: bool,
+    /// continue past info messages from pam without clicking next
+    pub auto_acknowledge_info: bool,
+    pub min_uid: u32,
+    pub max_uid: u32,
+    pub state_file: PathBuf,
+    pub theme: ThemeConfig,
+    pub background: BackgroundConfig,
+    pub fonts: FontConfig,
+    pub clock: ClockConfig,
+    pub locale: LocaleConfig,
+    pub keyboard: KeyboardConfig,
+    /// replaces the whole default layout when set
+    pub layout: Vec<LayoutEntry>,
+    /// the outputs the greeter's window spans, it is treated as one screen if there are none
+    pub outputs: Vec<OutputConfig>,
+    /// name of the output the login card is shown on, follows the mouse if not set
+    pub primary_output: Option<String>,
+    pub fullscreen: bool,
+    pub scale: ScaleConfig,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_session_name: None,
+            default_session_command: None,
+            user: None,
+            user_entry: UserEntry::default(),
+            password_reveal: true,
+            auto_acknowledge_info: false,
+            min_uid: 1000,
+            max_uid: 60000,
+            state_file: PathBuf::from("/var/cache/egui-greeter/state.json"),
+            theme: ThemeConfig::default(),
+            background: BackgroundConfig::default(),
+            fonts: FontConfig::default(),
+            clock: ClockConfig::default(),
+            locale: LocaleConfig::default(),
+            keyboard: KeyboardConfig::default(),
+            layout: default_layout(),
+            outputs: Vec::new(),
+            primary_output: None,
+            fullscreen: true,
+            scale: ScaleConfig::default(),
+        }
+    }
+}
+
+/// checked in order, the first one that exists is used
+const CONFIG_PATHS: [&str; 2] = [
+    "/etc/greetd/egui-greeter.toml",
+    "/etc/greetd/egui-greeter.json",
+];
+
+pub fn read_config_file(path: &Path) -> Result<toml::Table> {
+    let config = fs::read_to_string(path)
+        .with_context(|| format!("failed to read config file at {}", path.display()))?;
+
+    let config = if path.extension().is_some_and(|ext| ext == "json") {
+        serde_json::from_str(&config)
+            .with_context(|| format!("failed to deserialize config file at {}", path.display()))?
+    } else {
+        toml::from_str(&config)
+            .with_context(|| format!("failed to deserialize config file at {}", path.display()))?
+    };
+
+    Ok(config)
+}
+
+/// merge `over` into `base`, tables are merged key by key and everything else is replaced
+pub fn merge_tables(base: &mut toml::Table, over: toml::Table) {
+    for (key, value) in over {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(over)) => merge_tables(base, over),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// the drop in directory for `/etc/greetd/egui-greeter.toml` is `/etc/greetd/egui-greeter.d`
+fn drop_in_dir(config: &Path) -> PathBuf {
+    config.with_extension("d")
+}
+
+fn drop_in_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(err) => {
+            return Err(err).with_context(|| format!("failed to read {}", dir.display()));
+        }
+    };
+
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "toml"))
+        .collect();
+
+    files.sort();
+
+    Ok(files)
+}
+
+/// the main config file followed by its drop ins, in the order they are merged
+pub fn config_files(config: Option<&Path>) -> Result<Vec<PathBuf>> {
+    let main = match config {
+        Some(config) => config.to_path_buf(),
+        None => CONFIG_PATHS
+            .iter()
+            .map(PathBuf::from)
+            .find(|path| path.exists())
+            .unwrap_or_else(|| PathBuf::from(CONFIG_PATHS[0])),
+    };
+
+    let mut files = drop_in_files(&drop_in_dir(&main))?;
+
+    // the default config file doesn't need to exist, if only drop ins are used
+    if config.is_some() || main.exists() {
+        files.insert(0, main);
+    }
+
+    Ok(files)
 }
 
 impl Settings {
     pub fn from_args(args: Args) -> Result<Settings> {
-        let config_file: OnceCell<Settings> = OnceCell::new();
-
-        let config_file_fn = || -> Result<Settings> {
-            let config = match &args.config {
-                Some(config) => config.clone(),
-                None => PathBuf::from("/etc/greetd/egui-greeter.json"),
-            };
-
-            let settings = fs::read_to_string(&config).with_context(|| {
-                format!("failed to read config file at {}", config.to_str().unwrap())
-            })?;
-
-            let settings: Settings =
-                serde_json::from_str(&settings).with_context(|| "failed to deserialize config")?;
-
-            Ok(settings)
-        };
-
-        let default_session_name = match args.session_name {
-            Some(a) => a,
-            None => config_file
-                .get_or_try_init(config_file_fn)?
-                .default_session_name
-                .clone(),
-        };
-
-        let default_session_command = match args.session_command {
-            Some(a) => a,
-            None => config_file
-                .get_or_try_init(config_file_fn)?
-                .default_session_command
-                .clone(),
-        };
-
-        let user = match args.user {
-            Some(a) => a,
-            None => config_file.get_or_try_init(config_file_fn)?.user.clone(),
-        };
-
-        Ok(Settings {
-            default_session_name,
-            default_session_command,
-            user,
-        })
+        let mut config = toml::Table::new();
+
+        for file in config_files(args.config.as_deref())? {
+            merge_tables(&mut config, read_config_file(&file)?);
+        }
+
+        let mut settings = Settings::deserialize(config).with_context(|| "invalid config")?;
+
+        if let Some(name) = args.session_name {
+            settings.default_session_name = Some(name);
+        }
+
+        if let Some(command) = args.session_command {
+            settings.default_session_command = Some(command);
+        }
+
+        if let Some(user) = args.user {
+            settings.user = Some(user);
+        }
+
+        Ok(settings)
+    }
+
+    pub fn default_session(&self) -> Option<(String, String)> {
+        let command = self.default_session_command.clone()?;
+        let name = self
+            .default_session_name
+            .clone()
+            .unwrap_or_else(|| command.clone());
+
+        Some((name, command))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(toml: &str) -> toml::Table {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn merge_replaces_values_and_merges_tables() {
+        let mut config = table(
+            r#"
+            user = "alice"
+            [theme]
+            flavor = "mocha"
+            accent = "blue"
+            "#,
+        );
+
+        merge_tables(
+            &mut config,
+            table(
+                r#"
+                user = "bob"
+                [theme]
+                accent = "red"
+                "#,
+            ),
+        );
+
+        assert_eq!(
+            config,
+            table(
+                r#"
+                user = "bob"
+                [theme]
+                flavor = "mocha"
+                accent = "red"
+                "#,
+            )
+        );
+    }
+
+    #[test]
+    fn drop_ins_are_merged_after_the_main_file_in_name_order() {
+        let dir = std::env::temp_dir().join(format!("egui-greeter-test-{}", std::process::id()));
+        let main = dir.join("egui-greeter.toml");
+        let drop_ins = dir.join("egui-greeter.d");
+
+        fs::create_dir_all(&drop_ins).unwrap();
+        fs::write(&main, "user = \"main\"\nmin_uid = 500\n").unwrap();
+        fs::write(drop_ins.join("20-late.toml"), "user = \"late\"\n").unwrap();
+        fs::write(
+            drop_ins.join("10-early.toml"),
+            "user = \"early\"\nmax_uid = 2000\n",
+        )
+        .unwrap();
+        fs::write(drop_ins.join("ignored.json"), "{\"user\": \"json\"}").unwrap();
+
+        let files = config_files(Some(&main)).unwrap();
+
+        let mut config = toml::Table::new();
+
+        for file in &files {
+            merge_tables(&mut config, read_config_file(file).unwrap());
+        }
+
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            files,
+            [
+                main,
+                drop_ins.join("10-early.toml"),
+                drop_ins.join("20-late.toml")
+            ]
+        );
+        assert_eq!(
+            config,
+            table("user = \"late\"\nmin_uid = 500\nmax_uid = 2000\n")
+        );
     }
 }
@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// things remembered between boots, written after every successful login
+#[derive(Serialize, Deserialize, Default, Debug)]
+pub struct StateCache {
+    #[serde(skip)]
+    path: PathBuf,
+    pub last_user: Option<String>,
+    /// user name -> session name
+    #[serde(default)]
+    pub sessions: HashMap<String, String>,
+    /// user name -> keyboard layout
+    #[serde(default)]
+    pub keyboard_layouts: HashMap<String, String>,
+}
+
+impl StateCache {
+    pub fn load(path: &Path) -> StateCache {
+        let cache = fs::read_to_string(path)
+            .ok()
+            .and_then(|cache| match serde_json::from_str::<StateCache>(&cache) {
+                Ok(cache) => Some(cache),
+                Err(err) => {
+                    log::warn!("ignoring invalid state cache at {}: {err}", path.display());
+                    None
+                }
+            })
+            .unwrap_or_default();
+
+        StateCache {
+            path: path.to_path_buf(),
+            ..cache
+        }
+    }
+
+    pub fn session_for(&self, user: &str) -> Option<&str> {
+        self.sessions.get(user).map(String::as_str)
+    }
+
+    pub fn keyboard_layout_for(&self, user: &str) -> Option<&str> {
+        self.keyboard_layouts.get(user).map(String::as_str)
+    }
+
+    pub fn record_login(&mut self, user: &str, session: &str, keyboard_layout: Option<&str>) {
+        self.last_user = Some(user.to_string());
+        self.sessions.insert(user.to_string(), session.to_string());
+
+        if let Some(layout) = keyboard_layout {
+            self.keyboard_layouts
+                .insert(user.to_string(), layout.to_string());
+        }
+    }
+
+    pub fn save(&self) -> Result<()> {
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)
+                .with_context(|| format!("failed to create {}", dir.display()))?;
+        }
+
+        fs::write(&self.path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("failed to write state cache to {}", self.path.display()))?;
+
+        Ok(())
+    }
+}
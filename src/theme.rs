@@ -0,0 +1,214 @@
+use anyhow::{Result, anyhow};
+use catppuccin_egui::Theme;
+use egui::{Color32, Visuals};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// a colour written as `#rrggbb` or `#rrggbbaa` in the config
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HexColor(pub Color32);
+
+impl HexColor {
+    fn parse(hex: &str) -> Result<HexColor> {
+        let digits = hex
+            .strip_prefix('#')
+            .ok_or_else(|| anyhow!("colour `{hex}` should start with #"))?;
+
+        let channel = |i: usize| {
+            digits
+                .get(i..i + 2)
+                .and_then(|channel| u8::from_str_radix(channel, 16).ok())
+                .ok_or_else(|| anyhow!("invalid colour `{hex}`"))
+        };
+
+        match digits.len() {
+            6 => Ok(HexColor(Color32::from_rgb(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+            ))),
+            8 => Ok(HexColor(Color32::from_rgba_unmultiplied(
+                channel(0)?,
+                channel(2)?,
+                channel(4)?,
+                channel(6)?,
+            ))),
+            _ => Err(anyhow!("colour `{hex}` should be #rrggbb or #rrggbbaa")),
+        }
+    }
+}
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let [r, g, b, a] = self.0.to_srgba_unmultiplied();
+
+        serializer.serialize_str(&format!("#{r:02x}{g:02x}{b:02x}{a:02x}"))
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let hex = String::deserialize(deserializer)?;
+
+        HexColor::parse(&hex).map_err(serde::de::Error::custom)
+    }
+}
+
+/// the colours every widget is drawn with
+#[derive(Clone, Copy, Debug)]
+pub struct Palette {
+    /// behind everything
+    pub background: Color32,
+    /// the login card
+    pub card: Color32,
+    /// behind text fields and selector popups
+    pub input: Color32,
+    pub text: Color32,
+    /// typed in passwords and usernames
+    pub input_text: Color32,
+    /// the user and session selectors
+    pub selector_text: Color32,
+    /// buttons and highlights
+    pub accent: Color32,
+    /// text drawn on top of the accent colour
+    pub on_accent: Color32,
+    pub clock: Color32,
+    /// the power buttons
+    pub icons: Color32,
+    pub error: Color32,
+}
+
+impl Palette {
+    fn catppuccin(theme: Theme) -> Palette {
+        Palette {
+            background: theme.base,
+            card: theme.surface0,
+            input: theme.base,
+            text: theme.text,
+            input_text: theme.mauve,
+            selector_text: theme.pink,
+            accent: theme.lavender,
+            on_accent: theme.base,
+            clock: theme.teal,
+            icons: theme.teal,
+            error: theme.maroon,
+        }
+    }
+
+    const DARK: Palette = Palette {
+        background: Color32::from_rgb(27, 27, 27),
+        card: Color32::from_rgb(45, 45, 45),
+        input: Color32::from_rgb(20, 20, 20),
+        text: Color32::from_rgb(220, 220, 220),
+        input_text: Color32::from_rgb(180, 160, 240),
+        selector_text: Color32::from_rgb(240, 180, 220),
+        accent: Color32::from_rgb(90, 130, 220),
+        on_accent: Color32::from_rgb(20, 20, 20),
+        clock: Color32::from_rgb(120, 200, 190),
+        icons: Color32::from_rgb(120, 200, 190),
+        error: Color32::from_rgb(230, 80, 80),
+    };
+
+    const LIGHT: Palette = Palette {
+        background: Color32::from_rgb(248, 248, 248),
+        card: Color32::from_rgb(230, 230, 230),
+        input: Color32::from_rgb(255, 255, 255),
+        text: Color32::from_rgb(30, 30, 30),
+        input_text: Color32::from_rgb(60, 60, 160),
+        selector_text: Color32::from_rgb(140, 40, 110),
+        accent: Color32::from_rgb(80, 110, 200),
+        on_accent: Color32::from_rgb(255, 255, 255),
+        clock: Color32::from_rgb(40, 110, 100),
+        icons: Color32::from_rgb(40, 110, 100),
+        error: Color32::from_rgb(200, 30, 40),
+    };
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Default, PartialEq, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum Preset {
+    Latte,
+    Frappe,
+    #[default]
+    Macchiato,
+    Mocha,
+    Light,
+    Dark,
+}
+
+impl Preset {
+    fn palette(self) -> Palette {
+        match self {
+            Preset::Latte => Palette::catppuccin(catppuccin_egui::LATTE),
+            Preset::Frappe => Palette::catppuccin(catppuccin_egui::FRAPPE),
+            Preset::Macchiato => Palette::catppuccin(catppuccin_egui::MACCHIATO),
+            Preset::Mocha => Palette::catppuccin(catppuccin_egui::MOCHA),
+            Preset::Light => Palette::LIGHT,
+            Preset::Dark => Palette::DARK,
+        }
+    }
+}
+
+/// colours to use instead of the ones from the preset
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct PaletteOverrides {
+    pub background: Option<HexColor>,
+    pub card: Option<HexColor>,
+    pub input: Option<HexColor>,
+    pub text: Option<HexColor>,
+    pub input_text: Option<HexColor>,
+    pub selector_text: Option<HexColor>,
+    pub accent: Option<HexColor>,
+    pub on_accent: Option<HexColor>,
+    pub clock: Option<HexColor>,
+    pub icons: Option<HexColor>,
+    pub error: Option<HexColor>,
+}
+
+#[derive(Serialize, Deserialize, Default, Debug)]
+#[serde(default)]
+pub struct ThemeConfig {
+    pub preset: Preset,
+    pub colors: PaletteOverrides,
+}
+
+impl ThemeConfig {
+    pub fn palette(&self) -> Palette {
+        let preset = self.preset.palette();
+        let colors = &self.colors;
+        let pick = |color: Option<HexColor>, default: Color32| color.map_or(default, |c| c.0);
+
+        Palette {
+            background: pick(colors.background, preset.background),
+            card: pick(colors.card, preset.card),
+            input: pick(colors.input, preset.input),
+            text: pick(colors.text, preset.text),
+            input_text: pick(colors.input_text, preset.input_text),
+            selector_text: pick(colors.selector_text, preset.selector_text),
+            accent: pick(colors.accent, preset.accent),
+            on_accent: pick(colors.on_accent, preset.on_accent),
+            clock: pick(colors.clock, preset.clock),
+            icons: pick(colors.icons, preset.icons),
+            error: pick(colors.error, preset.error),
+        }
+    }
+
+    /// set up egui's own visuals to match the palette
+    pub fn apply(&self, ctx: &egui::Context, palette: &Palette) {
+        match self.preset {
+            Preset::Latte => catppuccin_egui::set_theme(ctx, catppuccin_egui::LATTE),
+            Preset::Frappe => catppuccin_egui::set_theme(ctx, catppuccin_egui::FRAPPE),
+            Preset::Macchiato => catppuccin_egui::set_theme(ctx, catppuccin_egui::MACCHIATO),
+            Preset::Mocha => catppuccin_egui::set_theme(ctx, catppuccin_egui::MOCHA),
+            Preset::Light => ctx.set_visuals(Visuals::light()),
+            Preset::Dark => ctx.set_visuals(Visuals::dark()),
+        }
+
+        ctx.style_mut(|style| {
+            style.visuals.panel_fill = palette.background;
+            style.visuals.window_fill = palette.background;
+            style.visuals.override_text_color = Some(palette.text);
+            style.visuals.error_fg_color = palette.error;
+        });
+    }
+}
@@ -1,49 +1,130 @@
-use chrono::{Datelike, Local, Timelike};
-use egui::{Color32, Label, RichText, Ui};
-
-static MONTHS: [&str; 12] = [
-    "January",
-    "Febuary",
-    "March",
-    "April",
-    "May",
-    "June",
-    "July",
-    "August",
-    "September",
-    "October",
-    "November",
-    "December",
-];
-
-static DAYS: [&str; 7] = [
-    "Monday",
-    "Tuesday",
-    "Wednesday",
-    "Thursday",
-    "Friday",
-    "Saturday",
-    "Sunday",
-];
-
-pub fn time_area(ui: &mut Ui) {
-    let time = Local::now();
-
-    let hour = time.hour();
-    let minute = time.minute();
-    let day_of_week = DAYS[time.weekday().num_days_from_monday() as usize];
-    let month = MONTHS[time.month0() as usize];
-    let day = time.day();
+use std::{
+    env,
+    ffi::CString,
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
+use egui::{Label, RichText, Ui};
+use serde::{Deserialize, Serialize};
+
+use crate::{fonts::FontSizes, theme::Palette};
+
+/// where the timezones named in the config are looked up
+pub const ZONEINFO: &str = "/usr/share/zoneinfo";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+#[serde(default)]
+pub struct ClockConfig {
+    /// strftime format for the time, replaces `twelve_hour` and `seconds` when set
+    pub time_format: Option<String>,
+    /// strftime format for the date line
+    pub date_format: String,
+    pub twelve_hour: bool,
+    pub seconds: bool,
+    /// a name from the tz database like `Europe/Berlin`, the system timezone is used if not set
+    pub timezone: Option<String>,
+}
+
+impl Default for ClockConfig {
+    fn default() -> Self {
+        Self {
+            time_format: None,
+            date_format: "%A %B %-d".to_string(),
+            twelve_hour: false,
+            seconds: false,
+            timezone: None,
+        }
+    }
+}
+
+impl ClockConfig {
+    pub fn time_format(&self) -> String {
+        if let Some(format) = &self.time_format {
+            return format.clone();
+        }
+
+        let seconds = if self.seconds { ":%S" } else { "" };
+
+        match self.twelve_hour {
+            true => format!("%-I:%M{seconds} %p"),
+            false => format!("%-H:%M{seconds}"),
+        }
+    }
+
+    /// whether either format shows seconds, so the clock has to be redrawn every second
+    fn shows_seconds(&self) -> bool {
+        [self.time_format(), self.date_format.clone()]
+            .iter()
+            .any(|format| ["%S", "%T", "%r", "%s"].iter().any(|s| format.contains(s)))
+    }
+
+    /// how long until the clock shows something else
+    pub fn until_next_tick(&self) -> Duration {
+        let period = if self.shows_seconds() { 1 } else { 60 };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+
+        // timezones are offset by whole minutes, so this lines up with the local time too
+        Duration::from_secs(period - now.as_secs() % period)
+            - Duration::from_nanos(now.subsec_nanos().into())
+    }
+
+    /// changes the environment of the process, so this has to be called before any other threads
+    /// are started
+    pub fn init_timezone(&self) {
+        if let Some(timezone) = &self.timezone {
+            if Path::new(ZONEINFO).join(timezone).is_file() {
+                // SAFETY: called from main before the auth thread or the ui are started
+                unsafe { env::set_var("TZ", timezone) };
+            } else {
+                log::warn!("unknown timezone {timezone}, using the system timezone");
+            }
+        }
+    }
+}
+
+/// format the current local time with strftime, so the names follow the locale
+fn format_now(format: &str) -> String {
+    let Ok(format) = CString::new(format) else {
+        return String::new();
+    };
+
+    let mut buffer = [0u8; 256];
+
+    // SAFETY: localtime_r and strftime only write to the tm and buffer given to them, and the
+    // length passed to strftime is the size of the buffer
+    let written = unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return String::new();
+        }
+
+        libc::strftime(
+            buffer.as_mut_ptr().cast(),
+            buffer.len(),
+            format.as_ptr(),
+            &tm,
+        )
+    };
+
+    String::from_utf8_lossy(&buffer[..written]).into_owned()
+}
+
+pub fn time_area(ui: &mut Ui, clock: &ClockConfig, palette: &Palette, sizes: &FontSizes) {
     ui.add(Label::new(
-        RichText::new(format!("{day_of_week} {month} {day}"))
-            .color(Color32::from_rgb(139, 213, 202))
-            .size(48.0),
+        RichText::new(format_now(&clock.date_format))
+            .color(palette.clock)
+            .size(sizes.date),
     ));
 
     ui.add(Label::new(
-        RichText::new(format!("{hour}:{:0>2}", minute))
-            .color(Color32::from_rgb(139, 213, 202))
-            .size(144.0),
+        RichText::new(format_now(&clock.time_format()))
+            .color(palette.clock)
+            .size(sizes.clock),
     ));
 }
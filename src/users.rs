@@ -0,0 +1,86 @@
+use std::{collections::HashSet, fs, path::PathBuf};
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct User {
+    pub name: String,
+    pub uid: u32,
+    pub gecos: String,
+    pub home: PathBuf,
+    pub shell: String,
+}
+
+impl User {
+    pub fn display_name(&self) -> &str {
+        match self.gecos.split(',').next() {
+            Some(name) if !name.is_empty() => name,
+            _ => &self.name,
+        }
+    }
+}
+
+fn parse_passwd_line(line: &str) -> Option<User> {
+    let mut fields = line.split(':');
+
+    let name = fields.next()?.to_string();
+    let _password = fields.next()?;
+    let uid = fields.next()?.parse().ok()?;
+    let _gid = fields.next()?;
+    let gecos = fields.next()?.to_string();
+    let home = PathBuf::from(fields.next()?);
+    let shell = fields.next()?.to_string();
+
+    Some(User {
+        name,
+        uid,
+        gecos,
+        home,
+        shell,
+    })
+}
+
+fn login_shells() -> Option<HashSet<String>> {
+    let shells = fs::read_to_string("/etc/shells").ok()?;
+
+    Some(
+        shells
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_string)
+            .collect(),
+    )
+}
+
+fn is_login_shell(shell: &str, shells: Option<&HashSet<String>>) -> bool {
+    if shell.ends_with("nologin") || shell.ends_with("false") {
+        return false;
+    }
+
+    match shells {
+        Some(shells) => shells.contains(shell),
+        None => true,
+    }
+}
+
+pub fn get_users(min_uid: u32, max_uid: u32) -> Vec<User> {
+    let passwd = match fs::read_to_string("/etc/passwd") {
+        Ok(passwd) => passwd,
+        Err(err) => {
+            log::warn!("failed to read /etc/passwd: {err}");
+            return Vec::new();
+        }
+    };
+
+    let shells = login_shells();
+
+    let mut users: Vec<User> = passwd
+        .lines()
+        .filter_map(parse_passwd_line)
+        .filter(|user| user.uid >= min_uid && user.uid <= max_uid)
+        .filter(|user| is_login_shell(&user.shell, shells.as_ref()))
+        .collect();
+
+    users.sort_by(|a, b| a.name.cmp(&b.name));
+
+    users
+}